@@ -4,10 +4,19 @@
 use std::env;
 mod modules;
 
-use modules::ssh_connection;
+use modules::{actions, duplicates, file_content, listing, media::{self, MediaSessions}, mime::MimeCache, permissions, port_forwarding::{self, PortForwards}, search, services, ssh_connection, storage::{self, StorageCache}, symlinks, system_status::{self, SystemStatusPoller}, tail::{self, FileTails}, terminal::{self, TerminalSessions}, thumbnails, trash, versions, watch::{self, DirectoryWatches}};
 
 fn main() {
     tauri::Builder::default()
+        .manage(MimeCache::default())
+        .manage(StorageCache::default())
+        .manage(MediaSessions::default())
+        .manage(TerminalSessions::default())
+        .manage(SystemStatusPoller::default())
+        .manage(FileTails::default())
+        .manage(DirectoryWatches::default())
+        .manage(PortForwards::default())
+        .register_uri_scheme_protocol("pi", media::handle_media_request)
         .invoke_handler(
           tauri::generate_handler![
             ssh_connection::connect_to_pi,
@@ -15,11 +24,52 @@ fn main() {
             ssh_connection::upload_files,
             ssh_connection::create_folder,
             ssh_connection::rename_file,
-            ssh_connection::delete_files,
-            ssh_connection::save_file,
-            ssh_connection::read_file,
             ssh_connection::get_storage_used,
             ssh_connection::get_file_sizes,
+            file_content::read_file,
+            file_content::save_file,
+            file_content::read_file_range,
+            listing::list_directory,
+            media::open_media_session,
+            media::get_media_metadata,
+            permissions::set_permissions,
+            permissions::chown,
+            storage::get_storage_report,
+            storage::get_storage_breakdown,
+            symlinks::create_symlink,
+            symlinks::read_link,
+            terminal::open_terminal,
+            terminal::write_terminal,
+            terminal::resize_terminal,
+            terminal::close_terminal,
+            thumbnails::get_thumbnails,
+            trash::delete_files,
+            trash::list_trash,
+            trash::restore_from_trash,
+            trash::empty_trash,
+            search::search_files,
+            search::grep_files,
+            versions::list_file_versions,
+            versions::read_file_version,
+            versions::diff_file_versions,
+            versions::restore_file_version,
+            duplicates::find_duplicates,
+            duplicates::resolve_duplicates,
+            actions::list_actions,
+            actions::run_action,
+            system_status::get_system_status,
+            system_status::start_system_status_polling,
+            system_status::stop_system_status_polling,
+            services::list_services,
+            services::manage_service,
+            services::get_service_logs,
+            tail::tail_file,
+            tail::stop_tail_file,
+            watch::watch_directory,
+            watch::unwatch_directory,
+            port_forwarding::open_port_forward,
+            port_forwarding::close_port_forward,
+            port_forwarding::list_port_forwards,
             ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,212 @@
+use std::{
+    env,
+    io::{ErrorKind, Read},
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use ssh2::Session;
+use tauri::{command, AppHandle, Manager};
+
+use super::{
+    ssh_connection::{get_ssh_session, shell_quote},
+    terminal::take_complete_utf8,
+    users::{find_user, User},
+};
+
+/// Struct to represent a remote action configured in `VITE_ACTIONS`.
+#[derive(Debug, Deserialize)]
+pub struct Action {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Shell command run on the Raspberry Pi
+    pub command: String,
+    /// Seconds before the command is stopped, defaulting to 60
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+/// Struct to represent an action a user may run, without its command.
+#[derive(Debug, Serialize)]
+pub struct ActionInfo {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Struct to represent output of a running action, emitted as an "action-output" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionOutput {
+    /// ID of the run, telling apart concurrent runs of the same action
+    pub run_id: u32,
+    pub action: String,
+    /// Either "stdout" or "stderr"
+    pub stream: String,
+    pub data: String,
+}
+
+/// Struct to represent how an action ended, emitted as an "action-finished" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionFinished {
+    /// ID of the run, as returned by `run_action`
+    pub run_id: u32,
+    /// Exit status of the command, or None if it was stopped or failed
+    pub exit_status: Option<i32>,
+    pub timed_out: bool,
+    /// Error that stopped following the command, or None if it ran to the end
+    pub error: Option<String>,
+}
+
+const DEFAULT_ACTION_TIMEOUT: u64 = 60;
+/// Exit statuses of `timeout` when it stops the command with SIGTERM, or kills it after `KILL_AFTER`
+const TIMEOUT_EXIT_STATUSES: [i32; 2] = [124, 137];
+/// Time the command is given to exit after being sent SIGTERM, before being killed
+const KILL_AFTER: u64 = 5;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// ID of the last action run, shared by all users.
+static LAST_RUN_ID: AtomicU32 = AtomicU32::new(0);
+
+//================================================================================================
+//                              Commands for remote actions
+//================================================================================================
+
+/// Command to list the remote actions the user may run.
+///
+/// * `Input`: User's name
+/// * `Output`: Names and descriptions of the allowed actions
+#[command]
+pub async fn list_actions(user_name: String) -> Result<Vec<ActionInfo>, String> {
+    let user = find_user(&user_name)?;
+    Ok(load_actions()?
+        .into_iter()
+        .filter(|action| is_action_allowed(&user, &action.name))
+        .map(|action| ActionInfo { name: action.name, description: action.description })
+        .collect())
+}
+
+/// Command to run a remote action configured in `VITE_ACTIONS`, if the user is allowed to.
+/// Returns as soon as the command starts. Output is emitted as "action-output" events tagged with the run's ID while the command runs,
+/// the command is stopped after its timeout, and an "action-finished" event with the same ID is emitted when it ends.
+///
+/// * `Input`: User's name, action name, and app handle for emitting events
+/// * `Output`: ID of the run
+#[command]
+pub async fn run_action(user_name: String, action_name: String, app_handle: AppHandle) -> Result<u32, String> {
+    let user = find_user(&user_name)?;
+    if !is_action_allowed(&user, &action_name) {
+        return Err(format!("User '{}' is not allowed to run action '{}'", user_name, action_name));
+    }
+    let action = load_actions()?
+        .into_iter()
+        .find(|action| action.name == action_name)
+        .ok_or_else(|| format!("Unknown action '{}'", action_name))?;
+    let timeout = action.timeout.unwrap_or(DEFAULT_ACTION_TIMEOUT);
+
+    let session = get_ssh_session().await?;
+    let run_id = LAST_RUN_ID.fetch_add(1, Ordering::Relaxed) + 1;
+    // Reading the channel blocks, so it runs on its own thread instead of holding up the async runtime
+    thread::spawn(move || {
+        let finished = run_action_command(run_id, &session, &action, timeout, &app_handle)
+            .unwrap_or_else(|error| ActionFinished { run_id, exit_status: None, timed_out: false, error: Some(error) });
+        app_handle.emit_all("action-finished", finished).unwrap();
+    });
+
+    Ok(run_id)
+}
+
+//================================================================================================
+//                              Helper functions for remote actions
+//================================================================================================
+
+/// Runs the command of an action on the Raspberry Pi, emitting its output until it exits or is stopped after its timeout.
+///
+/// * `Input`: Run ID, SSH session, action, timeout in seconds, and app handle for emitting events
+/// * `Output`: How the command ended
+fn run_action_command(run_id: u32, session: &Session, action: &Action, timeout: u64, app_handle: &AppHandle) -> Result<ActionFinished, String> {
+    let mut channel = session.channel_session().map_err(|e| format!("Failed to open channel: {}", e))?;
+    // `timeout` stops the command on the Pi, since closing the channel would leave it running
+    let command = format!("timeout -k {} {} sh -c {}", KILL_AFTER, timeout, shell_quote(&action.command));
+    channel.exec(&command).map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    session.set_blocking(false);
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(timeout + KILL_AFTER + 5);
+    let mut buffer = [0; 8192];
+    let (mut stdout, mut stderr) = (vec![], vec![]);
+    let mut stopped = false;
+    while !channel.eof() {
+        if Instant::now() > deadline {
+            stopped = true;
+            break;
+        }
+
+        let mut idle = true;
+        for (stream, pending) in [("stdout", &mut stdout), ("stderr", &mut stderr)] {
+            let read = if stream == "stdout" { channel.read(&mut buffer) } else { channel.stderr().read(&mut buffer) };
+            match read {
+                Ok(0) => {}
+                Ok(read) => {
+                    idle = false;
+                    pending.extend_from_slice(&buffer[..read]);
+                    emit_action_output(app_handle, run_id, &action.name, stream, take_complete_utf8(pending));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    session.set_blocking(true);
+                    return Err(format!("Failed to read from channel: {}", e));
+                }
+            }
+        }
+        if idle {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    session.set_blocking(true);
+
+    // Flush characters left incomplete at the end of the output
+    emit_action_output(app_handle, run_id, &action.name, "stdout", String::from_utf8_lossy(&stdout).into_owned());
+    emit_action_output(app_handle, run_id, &action.name, "stderr", String::from_utf8_lossy(&stderr).into_owned());
+
+    if stopped {
+        channel.close().map_err(|e| format!("Failed to close channel: {}", e))?;
+        return Ok(ActionFinished { run_id, exit_status: None, timed_out: true, error: None });
+    }
+    channel.wait_close().map_err(|e| format!("Failed to wait for channel close: {}", e))?;
+    let exit_status = channel.exit_status().map_err(|e| format!("Failed to get exit status: {}", e))?;
+    // The command may exit with the same statuses by itself, so they only mean a timeout once it has run for that long
+    let timed_out = started.elapsed() >= Duration::from_secs(timeout) && TIMEOUT_EXIT_STATUSES.contains(&exit_status);
+
+    Ok(ActionFinished { run_id, exit_status: if timed_out { None } else { Some(exit_status) }, timed_out, error: None })
+}
+
+/// Loads the remote actions from the `VITE_ACTIONS` JSON array in the .env file.
+///
+/// * `Output`: List of configured actions, empty if none are configured
+pub fn load_actions() -> Result<Vec<Action>, String> {
+    dotenv::dotenv().ok();
+    match env::var("VITE_ACTIONS") {
+        Ok(actions) => serde_json::from_str(&actions).map_err(|e| format!("Failed to parse VITE_ACTIONS: {}", e)),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Checks whether a user may run an action.
+///
+/// * `Input`: User and action name
+/// * `Output`: Whether the action is in the user's allow-list, or the user is an admin
+fn is_action_allowed(user: &User, action_name: &str) -> bool {
+    user.is_admin() || user.actions.iter().any(|action| action == action_name)
+}
+
+/// Emits output of a running action, skipping empty output.
+///
+/// * `Input`: App handle, run ID, action name, stream name, and output
+fn emit_action_output(app_handle: &AppHandle, run_id: u32, action: &str, stream: &str, data: String) {
+    if !data.is_empty() {
+        let output = ActionOutput { run_id, action: action.to_string(), stream: stream.to_string(), data };
+        app_handle.emit_all("action-output", output).unwrap();
+    }
+}
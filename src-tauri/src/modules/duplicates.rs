@@ -0,0 +1,223 @@
+use std::{cmp::Reverse, collections::{HashMap, HashSet}, path::Path};
+
+use chrono::Utc;
+use serde::Serialize;
+use ssh2::Session;
+use tauri::{command, State};
+
+use super::{
+    ssh_connection::{ensure_in_sandbox, get_remote_dirs_and_session, normalize_relative_path, run_remote_command, shell_quote},
+    storage::StorageCache,
+    trash::{move_to_trash, purge_expired_entries, verify_trash_directory},
+};
+
+/// Struct to represent files with identical contents.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// SHA-256 hash of the contents
+    pub hash: String,
+    /// Size of each file in bytes
+    pub size: u64,
+    /// Paths relative to the user's directory
+    pub paths: Vec<String>,
+}
+
+/// Struct to represent the duplicates found in the user's directory.
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    /// Groups of identical files, the ones wasting the most space first
+    pub groups: Vec<DuplicateGroup>,
+    /// Bytes freed by keeping a single file of each group
+    pub reclaimable: u64,
+}
+
+/// Struct to represent the space recovered by resolving duplicates.
+#[derive(Debug, Serialize)]
+pub struct DuplicatesResolved {
+    /// Bytes freed by replacing duplicates with hard links
+    pub freed: u64,
+    /// Bytes moved to the recycle bin, only freed once it is emptied or its items expire
+    pub trashed: u64,
+}
+
+/// Files larger than this are first compared by a hash of their beginning, to avoid hashing them in full.
+const PARTIAL_HASH_SIZE: u64 = 64 * 1024;
+const HASH_COMMAND_BATCH_SIZE: usize = 200;
+
+//================================================================================================
+//                              Commands for duplicate files
+//================================================================================================
+
+/// Command to find files with identical contents in the user's directory.
+/// Candidates are grouped by size, then compared by a hash of their first 64KB, then confirmed with `sha256sum`.
+/// Paths that are already hard links to the same file, i.e. with the same device and inode, are counted once.
+///
+/// * `Input`: User's name and minimum file size in bytes (default 1, skipping empty files)
+/// * `Output`: Duplicate groups and the space they waste
+#[command]
+pub async fn find_duplicates(user_name: String, min_size: Option<u64>) -> Result<DuplicateReport, String> {
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name, vec![]).await?;
+    let min_size = min_size.unwrap_or(1).max(1);
+
+    let (_, exit_status) = run_remote_command(&mut session, "command -v sha256sum")?;
+    if exit_status != 0 {
+        return Err("sha256sum is not available on the Raspberry Pi".to_string());
+    }
+
+    let find_command = format!("find {} -type f -printf '%s\\t%D:%i\\t%P\\0'", shell_quote(&remote_dir));
+    let (output, _) = run_remote_command(&mut session, &find_command)?;
+
+    let mut identities = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+    for entry in output.split('\0') {
+        let mut columns = entry.splitn(3, '\t');
+        let (size, identity, path) = match (columns.next(), columns.next(), columns.next()) {
+            (Some(size), Some(identity), Some(path)) => (size, identity, path),
+            _ => continue,
+        };
+        let size: u64 = size.parse().unwrap_or(0);
+        if size >= min_size && identities.insert(identity.to_string()) {
+            by_size.entry(size).or_default().push(path.to_string());
+        }
+    }
+    let candidates: Vec<(u64, Vec<String>)> = by_size.into_iter().filter(|(_, paths)| paths.len() > 1).collect();
+
+    // Large files that already differ in their first bytes are ruled out without reading them in full
+    let mut confirmed = vec![];
+    for (size, paths) in candidates {
+        let groups = if size > PARTIAL_HASH_SIZE {
+            group_by_hash(&mut session, &remote_dir, paths, true)?.into_values().filter(|paths| paths.len() > 1).collect()
+        } else {
+            vec![paths]
+        };
+        for paths in groups {
+            for (hash, mut paths) in group_by_hash(&mut session, &remote_dir, paths, false)? {
+                if paths.len() > 1 {
+                    paths.sort();
+                    confirmed.push(DuplicateGroup { hash, size, paths });
+                }
+            }
+        }
+    }
+
+    confirmed.sort_by_key(|group| Reverse(group.size * (group.paths.len() as u64 - 1)));
+    let reclaimable = confirmed.iter().map(|group| group.size * (group.paths.len() as u64 - 1)).sum();
+
+    Ok(DuplicateReport { groups: confirmed, reclaimable })
+}
+
+/// Command to get rid of duplicates of a file, either by moving them to the recycle bin or by replacing them with hard links to the kept file.
+/// Each duplicate is hashed again first and the command fails if any of them no longer matches the kept file.
+/// Hard-linked files share their contents, so saving one of them changes all of them.
+///
+/// * `Input`: User's name, path of the file to keep, paths of its duplicates (relative to the user's directory), action ("delete" or "hardlink"), and the storage cache
+/// * `Output`: Bytes freed by hard links, or bytes moved to the recycle bin
+#[command]
+pub async fn resolve_duplicates(user_name: String, keep: String, duplicates: Vec<String>, action: String, storage_cache: State<'_, StorageCache>) -> Result<DuplicatesResolved, String> {
+    if action != "delete" && action != "hardlink" {
+        return Err(format!("Unknown action '{}': expected 'delete' or 'hardlink'", action));
+    }
+    storage_cache.invalidate(&user_name);
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name.clone(), vec![]).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let mut paths = vec![];
+    for path in std::iter::once(&keep).chain(duplicates.iter()) {
+        let relative_path = normalize_relative_path(path)
+            .filter(|relative_path| !relative_path.is_empty())
+            .ok_or_else(|| format!("'{}' is outside of the user's directory", path))?;
+        let remote_path = format!("{}/{}", remote_dir, relative_path);
+        let stat = sftp.lstat(Path::new(&remote_path)).map_err(|e| format!("Failed to stat '{}': {}", relative_path, e))?;
+        if !stat.is_file() {
+            return Err(format!("'{}' is not a regular file", relative_path));
+        }
+        ensure_in_sandbox(&sftp, &remote_dir, &remote_path)?;
+        if paths.contains(&relative_path) {
+            return Err(format!("'{}' is listed more than once", relative_path));
+        }
+        paths.push(relative_path);
+    }
+    let size = sftp.stat(Path::new(&format!("{}/{}", remote_dir, paths[0])))
+        .map_err(|e| format!("Failed to stat '{}': {}", paths[0], e))?
+        .size
+        .unwrap_or(0);
+
+    let hashes = hash_files(&mut session, &remote_dir, &paths, false)?;
+    let keep_hash = hashes[0].clone().ok_or_else(|| format!("Failed to hash '{}'", paths[0]))?;
+    for (path, hash) in paths.iter().zip(&hashes).skip(1) {
+        if hash.as_ref() != Some(&keep_hash) {
+            return Err(format!("'{}' is no longer identical to '{}'", path, paths[0]));
+        }
+    }
+
+    let total = size * (paths.len() as u64 - 1);
+    if action == "delete" {
+        let trash_dir = verify_trash_directory(&mut session, &user_name)?;
+        purge_expired_entries(&sftp, &trash_dir)?;
+        let deleted_at = Utc::now();
+        for (index, path) in paths.iter().enumerate().skip(1) {
+            let id = format!("{}-{}", deleted_at.format("%Y%m%d%H%M%S%3f"), index);
+            move_to_trash(&sftp, &trash_dir, &format!("{}/{}", remote_dir, path), path.clone(), id, deleted_at)?;
+        }
+        Ok(DuplicatesResolved { freed: 0, trashed: total })
+    } else {
+        for path in paths.iter().skip(1) {
+            let command = format!("cd {} && ln -f -- {} {}", shell_quote(&remote_dir), shell_quote(&paths[0]), shell_quote(path));
+            let (_, exit_status) = run_remote_command(&mut session, &command)?;
+            if exit_status != 0 {
+                return Err(format!("Failed to replace '{}' with a hard link: ln exited with status {}", path, exit_status));
+            }
+        }
+        Ok(DuplicatesResolved { freed: total, trashed: 0 })
+    }
+}
+
+//================================================================================================
+//                              Helper functions for duplicate files
+//================================================================================================
+
+/// Groups files by the hash of their contents, leaving out files that could not be hashed.
+///
+/// * `Input`: SSH session, user's directory, paths relative to it, and whether to only hash the first 64KB
+/// * `Output`: Paths grouped by hash
+fn group_by_hash(session: &mut Session, remote_dir: &str, paths: Vec<String>, partial: bool) -> Result<HashMap<String, Vec<String>>, String> {
+    let hashes = hash_files(session, remote_dir, &paths, partial)?;
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for (path, hash) in paths.into_iter().zip(hashes) {
+        if let Some(hash) = hash {
+            groups.entry(hash).or_default().push(path);
+        }
+    }
+    Ok(groups)
+}
+
+/// Hashes files on the Raspberry Pi with `sha256sum`, in batches to keep commands short.
+/// The caller checks that `sha256sum` is available.
+///
+/// * `Input`: SSH session, user's directory, paths relative to it, and whether to only hash the first 64KB
+/// * `Output`: Hash of each file, or None for files that could not be read
+fn hash_files(session: &mut Session, remote_dir: &str, paths: &[String], partial: bool) -> Result<Vec<Option<String>>, String> {
+    // One line per file, in order, empty when the file cannot be read
+    let hash_command = if partial {
+        format!("head -c {} < \"$f\" | sha256sum", PARTIAL_HASH_SIZE)
+    } else {
+        "sha256sum < \"$f\"".to_string()
+    };
+    let mut hashes = vec![];
+    for batch in paths.chunks(HASH_COMMAND_BATCH_SIZE) {
+        let quoted_paths: Vec<String> = batch.iter().map(|path| shell_quote(path)).collect();
+        let command = format!(
+            "cd {} && for f in {}; do echo \"$({} 2>/dev/null | cut -c1-64)\"; done",
+            shell_quote(remote_dir),
+            quoted_paths.join(" "),
+            hash_command,
+        );
+        let (output, _) = run_remote_command(session, &command)?;
+        let lines: Vec<&str> = output.lines().collect();
+        if lines.len() != batch.len() {
+            return Err("Unexpected output from sha256sum command".to_string());
+        }
+        hashes.extend(lines.into_iter().map(|hash| Some(hash.trim().to_string()).filter(|hash| hash.len() == 64)));
+    }
+    Ok(hashes)
+}
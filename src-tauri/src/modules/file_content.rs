@@ -0,0 +1,430 @@
+use std::{env, io::{Read, Seek, SeekFrom, Write}, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{FileStat, Sftp};
+use tauri::{command, State};
+
+use super::{
+    ssh_connection::get_remote_dirs_and_session,
+    storage::StorageCache,
+    versions::{resolve_relative_file_path, store_version},
+};
+
+/// Struct to represent a range of bytes read from a remote file.
+#[derive(Debug, Serialize)]
+pub struct FileChunk {
+    /// Base64-encoded bytes of the range
+    pub data: String,
+    pub offset: u64,
+    /// Number of bytes in `data`
+    pub length: u64,
+    pub file_size: u64,
+    /// Encoding detected from the bytes of the range, ignoring a character it starts in the middle of
+    pub encoding: String,
+    /// True if fewer bytes were returned than requested because of the size limit
+    pub truncated: bool,
+}
+
+/// Struct to represent the text content of a file opened in the editor.
+#[derive(Debug, Serialize)]
+pub struct FileContent {
+    /// Decoded content with line endings normalized to `\n`
+    pub content: String,
+    /// Token identifying the version of the file that was read, passed back to `save_file`
+    pub version: String,
+    pub format: TextFormat,
+}
+
+/// How the text of a file is stored on the Raspberry Pi.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextFormat {
+    /// `utf-8`, `utf-16le`, `utf-16be` or `latin-1`
+    pub encoding: String,
+    /// Whether the file starts with a byte order mark
+    pub has_bom: bool,
+    /// `lf`, `crlf` or `cr`
+    pub line_ending: String,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        TextFormat { encoding: "utf-8".to_string(), has_bom: false, line_ending: "lf".to_string() }
+    }
+}
+
+/// Error returned by `save_file`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SaveError {
+    /// The file changed on the Raspberry Pi since it was read. `current_version` is None if it was deleted.
+    Conflict { current_version: Option<String> },
+    Failed { message: String },
+}
+
+impl From<String> for SaveError {
+    fn from(message: String) -> Self {
+        SaveError::Failed { message }
+    }
+}
+
+const DEFAULT_MAX_READ_SIZE: u64 = 10 * 1024 * 1024; // 10MB
+
+//================================================================================================
+//                              Commands for reading and saving file contents
+//================================================================================================
+
+/// Command to read the content of a file.
+/// The encoding, byte order mark and line ending style are detected and reported so `save_file` can preserve them.
+/// Files larger than `VITE_MAX_READ_SIZE` bytes are refused, since saving a partial file would lose the rest; `read_file_range` reads them in chunks.
+///
+/// * `Input`: User's name, current path, file name
+/// * `Output`: File content as a string, its version token, and its text format
+#[command]
+pub async fn read_file(user_name: String, current_path: Vec<String>, file_name: String) -> Result<FileContent, String> {
+    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+
+    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+    let path = Path::new(&remote_file_path);
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let mut remote_file = sftp.open(path).map_err(|e| format!("Failed to open file '{}': {}", remote_file_path, e))?;
+    let stat = remote_file.stat().map_err(|e| format!("Failed to stat file '{}': {}", remote_file_path, e))?;
+    let max_read_size = get_max_read_size();
+    if stat.size.unwrap_or(0) > max_read_size {
+        return Err(format!("File '{}' is larger than the {} bytes that can be opened at once", remote_file_path, max_read_size));
+    }
+
+    // The file may have grown since it was stat'ed
+    let mut bytes = vec![];
+    (&mut remote_file).take(max_read_size + 1).read_to_end(&mut bytes).map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
+    if bytes.len() as u64 > max_read_size {
+        return Err(format!("File '{}' is larger than the {} bytes that can be opened at once", remote_file_path, max_read_size));
+    }
+
+    let format = detect_text_format(&bytes)
+        .ok_or_else(|| format!("File '{}' is not a text file", remote_file_path))?;
+    let content = decode_text(&bytes, &format).replace("\r\n", "\n").replace('\r', "\n");
+
+    Ok(FileContent { content, version: version_token(&stat, &bytes), format })
+}
+
+/// Command to save the content to a file, keeping the previous content in the user's version history.
+/// Fails with a conflict if the file no longer matches `expected_version`, unless `force` is set.
+/// The version may only be omitted when creating a file that does not exist yet.
+/// The content is written in the given text format, or in the existing file's format if omitted.
+/// The existing file is read once, for the version check, its format and the version history.
+///
+/// * `Input`: User's name, current path, file name, file content, version token from `read_file`, whether to overwrite regardless, optional text format, and the storage cache
+/// * `Output`: Version token of the saved file
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_file(user_name: String, current_path: Vec<String>, file_name: String, file_content: String, expected_version: Option<String>, force: Option<bool>, format: Option<TextFormat>, storage_cache: State<'_, StorageCache>) -> Result<String, SaveError> {
+    storage_cache.invalidate(&user_name);
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, current_path.clone()).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_path = resolve_relative_file_path(&sftp, &remote_dir, &current_path, &file_name)?;
+    let remote_file_path = format!("{}/{}", remote_dir, relative_path);
+    let path = Path::new(&remote_file_path);
+
+    let existing = read_existing_file(&sftp, path)?;
+    if !force.unwrap_or(false) {
+        let current_version = existing.as_ref().map(|(stat, bytes)| version_token(stat, bytes));
+        if current_version != expected_version {
+            return Err(SaveError::Conflict { current_version });
+        }
+    }
+
+    let format = match format {
+        Some(format) => format,
+        // UTF-8 with `\n` line endings if the file does not exist or looks binary
+        None => existing.as_ref().and_then(|(_, bytes)| detect_text_format(bytes)).unwrap_or_default(),
+    };
+    let bytes = encode_text(&file_content, &format)?;
+
+    if let Some((_, previous)) = &existing {
+        store_version(&sftp, &remote_dir, &relative_path, previous)?;
+    }
+
+    let mut remote_file = sftp.create(path).map_err(|e| format!("Failed to create file '{}': {}", remote_file_path, e))?;
+    remote_file.write_all(&bytes).map_err(|e| format!("Failed to write to file '{}': {}", remote_file_path, e))?;
+    // The modification time is only final once the file is closed
+    drop(remote_file);
+    let stat = sftp.stat(path).map_err(|e| format!("Failed to stat file '{}': {}", remote_file_path, e))?;
+
+    Ok(version_token(&stat, &bytes))
+}
+
+/// Command to read a range of bytes from a file, without assuming it is text.
+/// Reads are capped at `VITE_MAX_READ_SIZE` bytes, in which case the chunk is flagged as truncated.
+///
+/// * `Input`: User's name, current path, file name, optional offset (defaults to 0) and optional length (defaults to the rest of the file)
+/// * `Output`: Requested bytes, base64-encoded, along with the file size and detected encoding
+#[command]
+pub async fn read_file_range(user_name: String, current_path: Vec<String>, file_name: String, offset: Option<u64>, length: Option<u64>) -> Result<FileChunk, String> {
+    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+
+    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+    let path = Path::new(&remote_file_path);
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let file_size = sftp.stat(path).map_err(|e| format!("Failed to stat remote file '{}': {}", remote_file_path, e))?
+        .size.unwrap_or(0);
+
+    let offset = offset.unwrap_or(0).min(file_size);
+    let requested = length.unwrap_or(file_size - offset).min(file_size - offset);
+    let max_read_size = get_max_read_size();
+    let length = requested.min(max_read_size);
+
+    let mut remote_file = sftp.open(path).map_err(|e| format!("Failed to open file '{}': {}", remote_file_path, e))?;
+    remote_file.seek(SeekFrom::Start(offset)).map_err(|e| format!("Failed to seek in file '{}': {}", remote_file_path, e))?;
+
+    let mut bytes = Vec::with_capacity(length as usize);
+    remote_file.take(length).read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
+
+    Ok(FileChunk {
+        data: STANDARD.encode(&bytes),
+        offset,
+        length: bytes.len() as u64,
+        file_size,
+        encoding: detect_encoding(&bytes[utf8_boundary(&bytes)..]).to_string(),
+        truncated: requested > max_read_size,
+    })
+}
+
+//================================================================================================
+//                              Helper functions for reading and saving file contents
+//================================================================================================
+
+/// Builds the version token of a file from its modification time, size and a SHA-256 hash of its content,
+/// so changes within the same second that keep the size are still told apart.
+///
+/// * `Input`: File stat and file content
+/// * `Output`: Version token
+fn version_token(stat: &FileStat, bytes: &[u8]) -> String {
+    let hash: String = Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}", stat.mtime.unwrap_or(0), stat.size.unwrap_or(0), hash)
+}
+
+/// Reads a remote file as it is now, if it exists.
+///
+/// * `Input`: SFTP session and remote file path
+/// * `Output`: File stat and content, or None if the file does not exist
+pub fn read_existing_file(sftp: &Sftp, path: &Path) -> Result<Option<(FileStat, Vec<u8>)>, String> {
+    let mut remote_file = match sftp.open(path) {
+        Ok(remote_file) => remote_file,
+        Err(_) if sftp.lstat(path).is_err() => return Ok(None),
+        Err(e) => return Err(format!("Failed to open file '{}': {}", path.display(), e)),
+    };
+    let stat = remote_file.stat().map_err(|e| format!("Failed to stat file '{}': {}", path.display(), e))?;
+    let mut bytes = vec![];
+    remote_file.read_to_end(&mut bytes).map_err(|e| format!("Failed to read file '{}': {}", path.display(), e))?;
+    Ok(Some((stat, bytes)))
+}
+
+/// Gets the maximum number of bytes returned by a single read.
+/// Read from `VITE_MAX_READ_SIZE`, defaulting to 10MB.
+///
+/// * `Output`: Maximum read size in bytes
+pub fn get_max_read_size() -> u64 {
+    dotenv::dotenv().ok();
+    env::var("VITE_MAX_READ_SIZE").ok()
+        .and_then(|size| size.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_READ_SIZE)
+}
+
+/// Detects the text format of a file from its contents.
+///
+/// * `Input`: Bytes of the file
+/// * `Output`: Text format, or None if the file looks binary
+pub fn detect_text_format(bytes: &[u8]) -> Option<TextFormat> {
+    let encoding = detect_encoding(bytes);
+    if encoding == "binary" {
+        return None;
+    }
+    let has_bom = bytes.starts_with(&[0xEF, 0xBB, 0xBF]) || encoding.starts_with("utf-16");
+    let format = TextFormat { encoding: encoding.to_string(), has_bom, line_ending: String::new() };
+
+    // Use the most common line ending, defaulting to `\n` for files with a single line
+    let text = decode_text(bytes, &format);
+    let crlf = text.matches("\r\n").count();
+    let cr = text.matches('\r').count() - crlf;
+    let lf = text.matches('\n').count() - crlf;
+    let line_ending = if crlf > lf && crlf >= cr {
+        "crlf"
+    } else if cr > lf && cr > crlf {
+        "cr"
+    } else {
+        "lf"
+    };
+
+    Some(TextFormat { line_ending: line_ending.to_string(), ..format })
+}
+
+/// Decodes the bytes of a file in the given text format, skipping any byte order mark.
+/// Invalid sequences are replaced rather than failing.
+///
+/// * `Input`: Bytes of the file and its text format
+/// * `Output`: Decoded text
+pub fn decode_text(bytes: &[u8], format: &TextFormat) -> String {
+    match format.encoding.as_str() {
+        "utf-16le" | "utf-16be" => {
+            let bytes = if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) { &bytes[2..] } else { bytes };
+            let units: Vec<u16> = bytes.chunks_exact(2)
+                .map(|pair| if format.encoding == "utf-16le" {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        "latin-1" => bytes.iter().map(|&b| b as char).collect(),
+        _ => {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF][..]).unwrap_or(bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// Encodes text in the given format, converting `\n`, `\r\n` and `\r` line endings to the format's style.
+///
+/// * `Input`: Text and target text format
+/// * `Output`: Bytes to write to the file
+pub fn encode_text(text: &str, format: &TextFormat) -> Result<Vec<u8>, String> {
+    let line_ending = match format.line_ending.as_str() {
+        "crlf" => "\r\n",
+        "cr" => "\r",
+        "lf" => "\n",
+        other => return Err(format!("Unsupported line ending '{}'", other)),
+    };
+    let text = text.replace("\r\n", "\n").replace('\r', "\n").replace('\n', line_ending);
+
+    let mut bytes = vec![];
+    match format.encoding.as_str() {
+        "utf-8" => {
+            if format.has_bom {
+                bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+            }
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        "utf-16le" | "utf-16be" => {
+            let little_endian = format.encoding == "utf-16le";
+            if format.has_bom {
+                bytes.extend_from_slice(if little_endian { &[0xFF, 0xFE] } else { &[0xFE, 0xFF] });
+            }
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() });
+            }
+        }
+        "latin-1" => {
+            for c in text.chars() {
+                let code = c as u32;
+                if code > 0xFF {
+                    return Err(format!("Character '{}' cannot be saved as latin-1", c));
+                }
+                bytes.push(code as u8);
+            }
+        }
+        other => return Err(format!("Unsupported encoding '{}'", other)),
+    }
+    Ok(bytes)
+}
+
+/// Finds the first UTF-8 character boundary of a range, skipping the continuation bytes of a character it starts in the middle of.
+///
+/// * `Input`: Bytes of the range
+/// * `Output`: Index of the first byte that is not a UTF-8 continuation byte, at most 3
+fn utf8_boundary(bytes: &[u8]) -> usize {
+    bytes.iter().take(3).take_while(|&&b| b & 0b1100_0000 == 0b1000_0000).count()
+}
+
+/// Detects the encoding of some bytes from their byte order mark and contents.
+///
+/// * `Input`: Bytes to inspect
+/// * `Output`: `utf-8`, `utf-16le`, `utf-16be`, `latin-1` or `binary`
+pub fn detect_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return "utf-8";
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return "utf-16le";
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return "utf-16be";
+    }
+    // Text rarely contains control characters other than whitespace and escape
+    if bytes.iter().any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)) {
+        return "binary";
+    }
+
+    // A range may end in the middle of a multi-byte character
+    match std::str::from_utf8(bytes) {
+        Ok(_) => "utf-8",
+        Err(e) if e.error_len().is_none() => "utf-8",
+        Err(_) => "latin-1",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_format(encoding: &str, has_bom: bool, line_ending: &str) -> TextFormat {
+        TextFormat { encoding: encoding.to_string(), has_bom, line_ending: line_ending.to_string() }
+    }
+
+    #[test]
+    fn detect_text_format_detects_the_encoding_and_byte_order_mark() {
+        assert_eq!(detect_text_format(b"plain\n"), Some(text_format("utf-8", false, "lf")));
+        assert_eq!(detect_text_format("caf\u{e9}\n".as_bytes()), Some(text_format("utf-8", false, "lf")));
+        assert_eq!(detect_text_format(b"\xEF\xBB\xBFbom\n"), Some(text_format("utf-8", true, "lf")));
+        assert_eq!(detect_text_format(b"caf\xE9\n"), Some(text_format("latin-1", false, "lf")));
+        assert_eq!(detect_text_format(b"\xFF\xFEa\x00\r\x00\n\x00"), Some(text_format("utf-16le", true, "crlf")));
+        assert_eq!(detect_text_format(b"\xFE\xFF\x00a\x00\n"), Some(text_format("utf-16be", true, "lf")));
+    }
+
+    #[test]
+    fn detect_text_format_uses_the_most_common_line_ending() {
+        assert_eq!(detect_text_format(b"a\r\nb\r\nc\n").unwrap().line_ending, "crlf");
+        assert_eq!(detect_text_format(b"a\rb\rc\n").unwrap().line_ending, "cr");
+        assert_eq!(detect_text_format(b"a\nb\nc\r\n").unwrap().line_ending, "lf");
+        assert_eq!(detect_text_format(b"single line").unwrap().line_ending, "lf");
+    }
+
+    #[test]
+    fn detect_text_format_rejects_binary_files() {
+        assert_eq!(detect_text_format(b"\x7fELF\x02\x01\x01\x00"), None);
+        assert_eq!(detect_text_format(b"text\x00with nul"), None);
+    }
+
+    #[test]
+    fn encode_text_restores_the_detected_format() {
+        for bytes in [&b"a\r\nb\r\n"[..], b"\xEF\xBB\xBFa\nb", b"caf\xE9\rx\r", b"\xFF\xFEa\x00\r\x00\n\x00"] {
+            let format = detect_text_format(bytes).unwrap();
+            let text = decode_text(bytes, &format).replace("\r\n", "\n").replace('\r', "\n");
+            assert_eq!(encode_text(&text, &format).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn detect_encoding_ignores_a_character_cut_at_the_start_of_a_range() {
+        let bytes = "\u{e9}t\u{e9}".as_bytes();
+        assert_eq!(utf8_boundary(&bytes[1..]), 1);
+        assert_eq!(detect_encoding(&bytes[1..][utf8_boundary(&bytes[1..])..]), "utf-8");
+        assert_eq!(utf8_boundary(b"abc"), 0);
+        assert_eq!(detect_encoding(b"caf\xE9!"), "latin-1");
+    }
+
+    #[test]
+    fn version_token_changes_with_the_content() {
+        let stat = FileStat { size: Some(5), uid: None, gid: None, perm: None, atime: None, mtime: Some(1700000000) };
+        let token = version_token(&stat, b"hello");
+        assert!(token.starts_with("1700000000-5-"));
+        assert_eq!(token, version_token(&stat, b"hello"));
+        assert_ne!(token, version_token(&stat, b"hallo"));
+    }
+}
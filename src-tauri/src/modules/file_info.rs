@@ -0,0 +1,237 @@
+use std::{collections::HashMap, io::Read, path::Path};
+
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use ssh2::{FileStat, Sftp};
+
+/// Struct to represent a file on the Raspberry Pi.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInfo {
+    pub name: String,
+    pub file_type: String,
+    /// MIME type detected from the file contents, filled in by `detect_mime_types`
+    pub mime_type: Option<String>,
+    pub kind: FileKind,
+    pub size: u64,
+    /// ISO-8601 modification time
+    pub last_modified: String,
+    /// ISO-8601 access time
+    pub last_accessed: String,
+    /// Permission bits, e.g. `0o755`
+    pub mode: u32,
+    /// Permission bits as shown by `ls -l`, e.g. `rwxr-xr-x`
+    pub permissions: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub symlink_target: Option<String>,
+    /// Kind of the entry a symlink points to, None if it is broken or not a symlink
+    pub symlink_target_kind: Option<FileKind>,
+    pub is_hidden: bool,
+}
+
+impl FileInfo {
+    /// Checks whether the entry is a folder or a symlink to one.
+    ///
+    /// * `Output`: True if the entry can be opened as a folder
+    pub fn is_folder(&self) -> bool {
+        self.kind == FileKind::Dir || (self.kind == FileKind::Symlink && self.symlink_target_kind == Some(FileKind::Dir))
+    }
+}
+
+/// Kind of entry a `FileInfo` describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// User and group names on the Raspberry Pi, keyed by uid and gid.
+#[derive(Debug, Default)]
+pub struct OwnerNames {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl OwnerNames {
+    /// Loads the user and group names from `/etc/passwd` and `/etc/group` on the Raspberry Pi.
+    /// Names are left unresolved if either file cannot be read.
+    ///
+    /// * `Input`: SFTP session
+    /// * `Output`: User and group names
+    pub fn load(sftp: &Sftp) -> OwnerNames {
+        OwnerNames {
+            users: read_id_names(sftp, "/etc/passwd"),
+            groups: read_id_names(sftp, "/etc/group"),
+        }
+    }
+
+    /// Looks up the uid of a user by name.
+    ///
+    /// * `Input`: User name
+    /// * `Output`: uid, or None if there is no such user
+    pub fn user_id(&self, name: &str) -> Option<u32> {
+        self.users.iter().find(|(_, user)| *user == name).map(|(uid, _)| *uid)
+    }
+
+    /// Looks up the gid of a group by name.
+    ///
+    /// * `Input`: Group name
+    /// * `Output`: gid, or None if there is no such group
+    pub fn group_id(&self, name: &str) -> Option<u32> {
+        self.groups.iter().find(|(_, group)| *group == name).map(|(gid, _)| *gid)
+    }
+}
+
+/// Builds the file information for a remote path from its SFTP stat.
+/// The stat is expected to come from `readdir` or `lstat` so that symlinks are not followed.
+///
+/// * `Input`: SFTP session, remote path, its stat, and the owner names
+/// * `Output`: File information, or None if the path has no file name
+pub fn file_info_from_stat(sftp: &Sftp, path: &Path, stat: &FileStat, owners: &OwnerNames) -> Option<FileInfo> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let kind = kind_from_stat(stat);
+
+    let file_type = match kind {
+        FileKind::File => path.extension()
+            .map_or_else(|| "Unknown".to_string(), |ext| ext.to_string_lossy().into_owned()),
+        FileKind::Dir => "Folder".to_string(),
+        FileKind::Symlink => "Symlink".to_string(),
+        FileKind::Other => "Other".to_string(),
+    };
+    let (symlink_target, symlink_target_kind) = if kind == FileKind::Symlink {
+        (
+            sftp.readlink(path).ok().map(|target| target.to_string_lossy().to_string()),
+            sftp.stat(path).ok().map(|target_stat| kind_from_stat(&target_stat)),
+        )
+    } else {
+        (None, None)
+    };
+    let mode = stat.perm.unwrap_or(0) & 0o7777;
+    let uid = stat.uid.unwrap_or(0);
+    let gid = stat.gid.unwrap_or(0);
+
+    Some(FileInfo {
+        is_hidden: name.starts_with('.'),
+        name,
+        file_type,
+        mime_type: None,
+        kind,
+        size: stat.size.unwrap_or(0),
+        last_modified: format_timestamp(stat.mtime.unwrap_or(0)),
+        last_accessed: format_timestamp(stat.atime.unwrap_or(0)),
+        mode,
+        permissions: format_permissions(mode),
+        uid,
+        gid,
+        owner: owners.users.get(&uid).cloned(),
+        group: owners.groups.get(&gid).cloned(),
+        symlink_target,
+        symlink_target_kind,
+    })
+}
+
+/// Gets the kind of entry a stat describes.
+///
+/// * `Input`: File stat
+/// * `Output`: Kind of entry
+pub fn kind_from_stat(stat: &FileStat) -> FileKind {
+    let file_type = stat.file_type();
+    if file_type.is_symlink() {
+        FileKind::Symlink
+    } else if file_type.is_dir() {
+        FileKind::Dir
+    } else if file_type.is_file() {
+        FileKind::File
+    } else {
+        FileKind::Other
+    }
+}
+
+/// Formats epoch seconds as an ISO-8601 timestamp in UTC.
+///
+/// * `Input`: Epoch seconds
+/// * `Output`: ISO-8601 timestamp
+pub fn format_timestamp(epoch_seconds: u64) -> String {
+    Utc.timestamp_opt(epoch_seconds as i64, 0)
+        .single()
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+/// Formats permission bits the way `ls -l` does, including setuid, setgid and sticky bits.
+///
+/// * `Input`: Permission bits
+/// * `Output`: Permission string, e.g. `rwxr-xr-x`
+fn format_permissions(mode: u32) -> String {
+    let mut permissions = String::with_capacity(9);
+    for (shift, special) in [(6, 0o4000), (3, 0o2000), (0, 0o1000)] {
+        let bits = (mode >> shift) & 0o7;
+        permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        let special_char = if special == 0o1000 { 't' } else { 's' };
+        permissions.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    permissions
+}
+
+/// Reads a colon-separated account file such as `/etc/passwd` into a map from id to name.
+///
+/// * `Input`: SFTP session and account file path
+/// * `Output`: Map from id to name, empty if the file cannot be read
+fn read_id_names(sftp: &Sftp, file_path: &str) -> HashMap<u32, String> {
+    let mut contents = String::new();
+    match sftp.open(Path::new(file_path)) {
+        Ok(mut file) => {
+            if file.read_to_string(&mut contents).is_err() {
+                return HashMap::new();
+            }
+        }
+        Err(_) => return HashMap::new(),
+    }
+
+    // Each line is `name:password:id:...`
+    contents.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_permissions_matches_ls() {
+        assert_eq!(format_permissions(0o755), "rwxr-xr-x");
+        assert_eq!(format_permissions(0o640), "rw-r-----");
+        assert_eq!(format_permissions(0o000), "---------");
+    }
+
+    #[test]
+    fn format_permissions_shows_special_bits() {
+        assert_eq!(format_permissions(0o4755), "rwsr-xr-x");
+        assert_eq!(format_permissions(0o2644), "rw-r-Sr--");
+        assert_eq!(format_permissions(0o1777), "rwxrwxrwt");
+        assert_eq!(format_permissions(0o1666), "rw-rw-rwT");
+    }
+
+    #[test]
+    fn format_timestamp_uses_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00+00:00");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14T22:13:20+00:00");
+    }
+}
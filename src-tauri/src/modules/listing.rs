@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+use super::{
+    file_info::FileInfo,
+    mime::{detect_mime_types, MimeCache},
+    ssh_connection::{get_remote_dirs_and_session, list_files_in_directory},
+};
+
+/// Key to sort a directory listing by.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Type,
+}
+
+/// Options for sorting, filtering and paginating a directory listing.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ListOptions {
+    /// Sorts by name if omitted
+    pub sort_by: Option<SortKey>,
+    pub descending: bool,
+    pub hide_hidden: bool,
+    /// Case-insensitive substring the file names must contain
+    pub name_filter: Option<String>,
+    pub offset: usize,
+    /// Maximum number of entries to return, all remaining entries if omitted
+    pub limit: Option<usize>,
+}
+
+/// Struct to represent one page of a directory listing.
+#[derive(Debug, Serialize)]
+pub struct DirectoryPage {
+    pub files: Vec<FileInfo>,
+    /// Number of entries matching the filters, across all pages
+    pub total: usize,
+    pub offset: usize,
+}
+
+//================================================================================================
+//                              Commands for directory listings
+//================================================================================================
+
+/// Command to list one page of a directory in the user's folder.
+/// Folders, and links to folders, are always listed before files, then entries are ordered by the sort key.
+///
+/// * `Input`: User's name, current path, listing options, and the MIME type cache
+/// * `Output`: Requested page of the directory listing and the total number of matching entries
+#[command]
+pub async fn list_directory(user_name: String, current_path: Vec<String>, options: Option<ListOptions>, mime_cache: State<'_, MimeCache>) -> Result<DirectoryPage, String> {
+    let (mut session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let options = options.unwrap_or_default();
+
+    let files = list_files_in_directory(&mut session, &current_remote_dir)?;
+    let mut page = paginate(files, &options);
+    detect_mime_types(&mut session, &current_remote_dir, &mut page.files, &mime_cache)?;
+
+    Ok(page)
+}
+
+//================================================================================================
+//                              Helper functions for directory listings
+//================================================================================================
+
+/// Filters, sorts and paginates a directory listing.
+///
+/// * `Input`: All entries of the directory and listing options
+/// * `Output`: Requested page of the directory listing
+fn paginate(files: Vec<FileInfo>, options: &ListOptions) -> DirectoryPage {
+    let name_filter = options.name_filter.as_ref().map(|filter| filter.to_lowercase());
+    let mut files: Vec<FileInfo> = files.into_iter()
+        .filter(|file| !(options.hide_hidden && file.is_hidden))
+        .filter(|file| name_filter.as_ref().map_or(true, |filter| file.name.to_lowercase().contains(filter)))
+        .collect();
+
+    files.sort_by(|a, b| {
+        let folders_first = b.is_folder().cmp(&a.is_folder());
+        let ordering = match options.sort_by.unwrap_or(SortKey::Name) {
+            SortKey::Name => compare_names(a, b),
+            SortKey::Size => a.size.cmp(&b.size).then_with(|| compare_names(a, b)),
+            // ISO-8601 timestamps in UTC sort chronologically as strings
+            SortKey::Modified => a.last_modified.cmp(&b.last_modified).then_with(|| compare_names(a, b)),
+            SortKey::Type => a.file_type.to_lowercase().cmp(&b.file_type.to_lowercase()).then_with(|| compare_names(a, b)),
+        };
+        folders_first.then(if options.descending { ordering.reverse() } else { ordering })
+    });
+
+    let total = files.len();
+    let files = files.into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    DirectoryPage { files, total, offset: options.offset }
+}
+
+/// Compares two entries by name, ignoring case.
+///
+/// * `Input`: Entries to compare
+/// * `Output`: Ordering of the entries
+fn compare_names(a: &FileInfo, b: &FileInfo) -> Ordering {
+    a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| a.name.cmp(&b.name))
+}
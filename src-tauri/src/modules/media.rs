@@ -0,0 +1,495 @@
+use std::{collections::HashMap, error::Error, io::{Cursor, Read, Seek, SeekFrom}, path::Path, sync::Mutex};
+
+use chrono::NaiveDateTime;
+use exif::{Exif, In, Reader, Tag, Value};
+use serde::Serialize;
+use ssh2::Session;
+use tauri::{
+    command,
+    http::{Request, Response, ResponseBuilder},
+    AppHandle, Manager, State,
+};
+
+use super::{
+    mime::sniff_mime_type,
+    ssh_connection::{ensure_in_sandbox, get_remote_dirs_and_session, normalize_relative_path, run_remote_command, shell_quote},
+};
+
+/// Struct to represent the metadata of an image, audio or video file.
+#[derive(Debug, Serialize)]
+pub struct MediaMetadata {
+    pub mime_type: String,
+    /// Stored width and height in pixels, before applying the EXIF orientation
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF fields, for images that have them
+    pub exif: Option<ExifMetadata>,
+    /// Stream information from `ffprobe`, for audio and video when it is installed on the Raspberry Pi
+    pub streams: Option<StreamMetadata>,
+}
+
+/// Struct to represent the EXIF fields of a photo.
+#[derive(Debug, Serialize)]
+pub struct ExifMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    /// When the photo was taken, in ISO format, with a timezone offset if the camera recorded one
+    pub capture_date: Option<String>,
+    /// EXIF orientation, from 1 (upright) to 8
+    pub orientation: Option<u32>,
+    pub gps: Option<GpsPosition>,
+    /// Exposure settings formatted for display, e.g. "1/250 s", "f/2.8", "4.2 mm"
+    pub exposure_time: Option<String>,
+    pub f_number: Option<String>,
+    pub focal_length: Option<String>,
+    pub iso: Option<u32>,
+}
+
+/// Struct to represent where a photo was taken, in decimal degrees and meters.
+#[derive(Debug, Serialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// Struct to represent the container of an audio or video file.
+#[derive(Debug, Serialize)]
+pub struct StreamMetadata {
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    pub format: Option<String>,
+    /// Bit rate in bits per second
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<MediaStream>,
+}
+
+/// Struct to represent an audio, video or subtitle stream of a media file.
+#[derive(Debug, Serialize)]
+pub struct MediaStream {
+    /// Either "video", "audio", "subtitle" or "data"
+    pub kind: String,
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+}
+
+/// SSH sessions reused across media requests, keyed by user, since players send many range requests while seeking.
+#[derive(Default)]
+pub struct MediaSessions(Mutex<HashMap<String, (Session, String)>>);
+
+/// Most bytes sent in response to any request; players request the rest as they need it
+const MAX_RANGE_SIZE: u64 = 4 * 1024 * 1024;
+const SNIFF_SIZE: usize = 512;
+/// EXIF data and image dimensions are stored near the start of the file
+const METADATA_READ_SIZE: u64 = 256 * 1024;
+
+//================================================================================================
+//                              Protocol for streaming media
+//================================================================================================
+
+/// Handles a request to the `pi://` protocol, streaming a file from the user's directory.
+/// URLs have the form `pi://localhost/<user>/<path relative to the user's directory>`, percent-encoded as by `convertFileSrc`.
+/// Supports HTTP range requests so media elements can seek without downloading the whole file.
+/// The handler runs on the main thread, so every response is a partial one of at most 4MB, even without a `Range` header.
+///
+/// * `Input`: App handle and the request
+/// * `Output`: Response with the requested bytes, or an error status with a message
+pub fn handle_media_request(app_handle: &AppHandle, request: &Request) -> Result<Response, Box<dyn Error>> {
+    match serve_media(app_handle, request) {
+        Ok(response) => Ok(response),
+        Err((status, message)) => ResponseBuilder::new().status(status).mimetype("text/plain").body(message.into_bytes()),
+    }
+}
+
+//================================================================================================
+//                              Commands for media metadata
+//================================================================================================
+
+/// Command to open the SSH session used to stream the user's media through the `pi://` protocol, if it is not open yet.
+/// Must be called before loading media URLs, since the protocol handler cannot connect without blocking the UI.
+///
+/// * `Input`: User's name and the media sessions
+/// * `Output`: None
+#[command]
+pub async fn open_media_session(user_name: String, media_sessions: State<'_, MediaSessions>) -> Result<(), String> {
+    let user_name = user_name.to_lowercase();
+    let is_open = media_sessions.0.lock().map_err(|_| "Media sessions are poisoned".to_string())?.contains_key(&user_name);
+    if !is_open {
+        let (session, remote_dir, _) = get_remote_dirs_and_session(user_name.clone(), vec![]).await?;
+        media_sessions.0.lock().map_err(|_| "Media sessions are poisoned".to_string())?.insert(user_name, (session, remote_dir));
+    }
+    Ok(())
+}
+
+/// Command to get the metadata of an image, audio or video file in the current directory.
+/// EXIF fields and image dimensions are parsed from the start of the file, and audio and video are probed with `ffprobe` when it is installed.
+///
+/// * `Input`: User's name, current path, and file name
+/// * `Output`: Media metadata
+#[command]
+pub async fn get_media_metadata(user_name: String, current_path: Vec<String>, file_name: String) -> Result<MediaMetadata, String> {
+    let (mut session, remote_dir, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+
+    let header = {
+        let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+        ensure_in_sandbox(&sftp, &remote_dir, &remote_file_path)?;
+        let remote_file = sftp.open(Path::new(&remote_file_path)).map_err(|e| format!("Failed to open remote file '{}': {}", remote_file_path, e))?;
+        let mut header = vec![];
+        remote_file.take(METADATA_READ_SIZE).read_to_end(&mut header)
+            .map_err(|e| format!("Failed to read remote file '{}': {}", remote_file_path, e))?;
+        header
+    };
+    let mime_type = sniff_mime_type(&header).to_string();
+
+    let mut metadata = MediaMetadata { mime_type, width: None, height: None, exif: None, streams: None };
+    if metadata.mime_type.starts_with("image/") {
+        let exif = Reader::new().read_from_container(&mut Cursor::new(&header)).ok();
+        let dimensions = image::io::Reader::new(Cursor::new(&header))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok());
+        let exif_dimensions = exif.as_ref().and_then(|exif| Some((uint_field(exif, Tag::PixelXDimension)?, uint_field(exif, Tag::PixelYDimension)?)));
+        if let Some((width, height)) = dimensions.or(exif_dimensions) {
+            metadata.width = Some(width);
+            metadata.height = Some(height);
+        }
+        metadata.exif = exif.as_ref().map(parse_exif);
+    } else if metadata.mime_type.starts_with("video/") || metadata.mime_type.starts_with("audio/") {
+        metadata.streams = probe_media(&mut session, &remote_file_path)?;
+        let video = metadata.streams.iter().flat_map(|streams| &streams.streams).find(|stream| stream.kind == "video");
+        if let Some(video) = video {
+            metadata.width = video.width;
+            metadata.height = video.height;
+        }
+    }
+
+    Ok(metadata)
+}
+
+//================================================================================================
+//                              Helper functions for streaming media
+//================================================================================================
+
+/// Serves a media request with the user's session, dropping the session if it fails so it is reopened by `open_media_session`.
+/// The protocol handler runs on the main thread, so it never connects itself and only holds the lock while looking up the session.
+///
+/// * `Input`: App handle and the request
+/// * `Output`: Response, or an HTTP status and message
+fn serve_media(app_handle: &AppHandle, request: &Request) -> Result<Response, (u16, String)> {
+    let (user_name, relative_path) = parse_media_uri(request.uri()).ok_or_else(|| (400, format!("Invalid media URL '{}'", request.uri())))?;
+    let range = request.headers().get("range").and_then(|value| value.to_str().ok()).map(|value| value.to_string());
+
+    let media_sessions = app_handle.state::<MediaSessions>();
+    let (session, remote_dir) = media_sessions.0.lock()
+        .map_err(|_| (500, "Media sessions are poisoned".to_string()))?
+        .get(&user_name)
+        .cloned()
+        .ok_or_else(|| (503, format!("No media session is open for '{}'", user_name)))?;
+
+    let result = read_media(&session, &remote_dir, &relative_path, range.as_deref());
+    if let Err((502, _)) = result {
+        if let Ok(mut sessions) = media_sessions.0.lock() {
+            sessions.remove(&user_name);
+        }
+    }
+    result
+}
+
+/// Reads the requested range of a file in the user's directory, capped at `MAX_RANGE_SIZE`, and builds the response.
+/// Requests without a `Range` header are answered as if they asked for the file from its start.
+///
+/// * `Input`: SSH session, user's directory, path relative to it, and the `Range` header if any
+/// * `Output`: Response, or an HTTP status and message
+fn read_media(session: &Session, remote_dir: &str, relative_path: &str, range: Option<&str>) -> Result<Response, (u16, String)> {
+    let sftp = session.sftp().map_err(|e| (502, format!("Failed to create SFTP session: {}", e)))?;
+    let remote_file_path = format!("{}/{}", remote_dir, relative_path);
+    let stat = sftp.stat(Path::new(&remote_file_path)).map_err(|e| (404, format!("Failed to stat '{}': {}", relative_path, e)))?;
+    ensure_in_sandbox(&sftp, remote_dir, &remote_file_path).map_err(|e| (403, e))?;
+    if !stat.is_file() {
+        return Err((404, format!("'{}' is not a file", relative_path)));
+    }
+    let size = stat.size.unwrap_or(0);
+
+    let (start, end) = match range {
+        Some(range) => parse_range(range, size).ok_or_else(|| (416, format!("Invalid range '{}' for a file of {} bytes", range, size)))?,
+        None => (0, size.saturating_sub(1)),
+    };
+    let end = end.min(start + MAX_RANGE_SIZE - 1);
+
+    let mut remote_file = sftp.open(Path::new(&remote_file_path)).map_err(|e| (502, format!("Failed to open remote file '{}': {}", relative_path, e)))?;
+    let mut header = vec![0; SNIFF_SIZE];
+    let header_length = remote_file.read(&mut header).map_err(|e| (502, format!("Failed to read remote file '{}': {}", relative_path, e)))?;
+    let mime_type = sniff_mime_type(&header[..header_length]);
+
+    let mut bytes = vec![];
+    if size > 0 {
+        remote_file.seek(SeekFrom::Start(start)).map_err(|e| (502, format!("Failed to seek in remote file '{}': {}", relative_path, e)))?;
+        remote_file.take(end - start + 1).read_to_end(&mut bytes)
+            .map_err(|e| (502, format!("Failed to read remote file '{}': {}", relative_path, e)))?;
+    }
+
+    // An empty file has no range to send, so it is the only full response
+    if bytes.is_empty() {
+        if range.is_some() {
+            return Err((416, format!("Range is past the end of '{}'", relative_path)));
+        }
+        return ResponseBuilder::new()
+            .status(200)
+            .mimetype(mime_type)
+            .header("Accept-Ranges", "bytes")
+            .header("Content-Length", "0")
+            .body(bytes)
+            .map_err(|e| (500, format!("Failed to build response: {}", e)));
+    }
+
+    ResponseBuilder::new()
+        .status(206)
+        .mimetype(mime_type)
+        .header("Accept-Ranges", "bytes")
+        .header("Content-Length", bytes.len().to_string())
+        .header("Content-Range", format!("bytes {}-{}/{}", start, start + bytes.len() as u64 - 1, size))
+        .body(bytes)
+        .map_err(|e| (500, format!("Failed to build response: {}", e)))
+}
+
+/// Parses a media URL into the user's name and the file path relative to their directory.
+/// On Windows the webview rewrites custom protocols to `https://pi.localhost/`, so only the path after the host is used.
+///
+/// * `Input`: Request URI
+/// * `Output`: User's name and relative path, or None if the URL is invalid or leads outside the user's directory
+fn parse_media_uri(uri: &str) -> Option<(String, String)> {
+    let after_scheme = uri.split_once("://")?.1;
+    let path = after_scheme.split_once('/')?.1;
+    let path = path.split(['?', '#']).next()?;
+    let path = percent_decode(path)?;
+
+    let (user_name, relative_path) = path.trim_start_matches('/').split_once('/')?;
+    let relative_path = normalize_relative_path(relative_path).filter(|relative_path| !relative_path.is_empty())?;
+    if user_name.is_empty() || user_name.contains("..") {
+        return None;
+    }
+    Some((user_name.to_string(), relative_path))
+}
+
+/// Parses the first range of a `Range` header, as `bytes=start-end`, `bytes=start-` or `bytes=-suffix`.
+///
+/// * `Input`: Header value and file size
+/// * `Output`: Inclusive start and end offsets, or None if the range cannot be satisfied
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = range.trim().strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+    if size == 0 {
+        return None;
+    }
+
+    if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        return Some((size.saturating_sub(suffix), size - 1));
+    }
+    let start: u64 = start.parse().ok()?;
+    let end = if end.is_empty() { size - 1 } else { end.parse::<u64>().ok()?.min(size - 1) };
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Decodes `%XX` escapes in a URL path.
+///
+/// * `Input`: Percent-encoded path
+/// * `Output`: Decoded path, or None if it is malformed or not UTF-8
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = path.get(index + 1..index + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Extracts the commonly displayed fields from parsed EXIF data.
+///
+/// * `Input`: EXIF data
+/// * `Output`: EXIF metadata
+fn parse_exif(exif: &Exif) -> ExifMetadata {
+    let display = |tag| exif.get_field(tag, In::PRIMARY).map(|field| field.display_value().with_unit(exif).to_string());
+
+    let capture_date = ascii_field(exif, Tag::DateTimeOriginal)
+        .or_else(|| ascii_field(exif, Tag::DateTime))
+        .and_then(|date| NaiveDateTime::parse_from_str(&date, "%Y:%m:%d %H:%M:%S").ok())
+        .map(|date| {
+            let offset = ascii_field(exif, Tag::OffsetTimeOriginal).unwrap_or_default();
+            format!("{}{}", date.format("%Y-%m-%dT%H:%M:%S"), offset)
+        });
+
+    let gps = match (gps_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"), gps_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W")) {
+        (Some(latitude), Some(longitude)) => {
+            let altitude = match exif.get_field(Tag::GPSAltitude, In::PRIMARY).map(|field| &field.value) {
+                Some(Value::Rational(values)) if !values.is_empty() => {
+                    // A reference of 1 means below sea level
+                    let below = uint_field(exif, Tag::GPSAltitudeRef) == Some(1);
+                    Some(if below { -values[0].to_f64() } else { values[0].to_f64() })
+                }
+                _ => None,
+            };
+            Some(GpsPosition { latitude, longitude, altitude })
+        }
+        _ => None,
+    };
+
+    ExifMetadata {
+        camera_make: ascii_field(exif, Tag::Make),
+        camera_model: ascii_field(exif, Tag::Model),
+        lens_model: ascii_field(exif, Tag::LensModel),
+        capture_date,
+        orientation: uint_field(exif, Tag::Orientation),
+        gps,
+        exposure_time: display(Tag::ExposureTime),
+        f_number: display(Tag::FNumber),
+        focal_length: display(Tag::FocalLength),
+        iso: uint_field(exif, Tag::PhotographicSensitivity),
+    }
+}
+
+/// Gets an EXIF text field, trimmed of the padding some cameras add.
+///
+/// * `Input`: EXIF data and tag
+/// * `Output`: Text, or None if the field is missing or empty
+fn ascii_field(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => {
+            let text = String::from_utf8_lossy(values.first()?).trim().to_string();
+            Some(text).filter(|text| !text.is_empty())
+        }
+        _ => None,
+    }
+}
+
+/// Gets an EXIF integer field.
+///
+/// * `Input`: EXIF data and tag
+/// * `Output`: Integer, or None if the field is missing
+fn uint_field(exif: &Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+/// Converts an EXIF GPS coordinate from degrees, minutes and seconds to signed decimal degrees.
+///
+/// * `Input`: EXIF data, coordinate tag, reference tag, and the reference that makes the coordinate negative
+/// * `Output`: Decimal degrees, or None if the coordinate is missing
+fn gps_coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: &str) -> Option<f64> {
+    let values = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(values) if values.len() >= 3 => values,
+        _ => return None,
+    };
+    let degrees = values[0].to_f64() + values[1].to_f64() / 60.0 + values[2].to_f64() / 3600.0;
+    let negative = ascii_field(exif, ref_tag).as_deref() == Some(negative_ref);
+    Some(if negative { -degrees } else { degrees })
+}
+
+/// Probes an audio or video file with `ffprobe` on the Raspberry Pi.
+///
+/// * `Input`: SSH session and remote file path
+/// * `Output`: Stream metadata, or None if `ffprobe` is not installed or cannot read the file
+fn probe_media(session: &mut Session, remote_file_path: &str) -> Result<Option<StreamMetadata>, String> {
+    let command = format!("ffprobe -v quiet -print_format json -show_format -show_streams {}", shell_quote(remote_file_path));
+    let (output, exit_status) = run_remote_command(session, &command)?;
+    if exit_status != 0 {
+        return Ok(None);
+    }
+    let probe: serde_json::Value = serde_json::from_str(&output).map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    // ffprobe reports most numbers as strings
+    let number = |value: &serde_json::Value| value.as_str().and_then(|value| value.parse::<f64>().ok()).or_else(|| value.as_f64());
+    let frame_rate = |value: &serde_json::Value| {
+        let (numerator, denominator) = value.as_str()?.split_once('/')?;
+        let (numerator, denominator): (f64, f64) = (numerator.parse().ok()?, denominator.parse().ok()?);
+        Some(numerator / denominator).filter(|rate| rate.is_finite() && *rate > 0.0)
+    };
+
+    let format = &probe["format"];
+    let streams = probe["streams"].as_array().map(Vec::as_slice).unwrap_or_default().iter().map(|stream| MediaStream {
+        kind: stream["codec_type"].as_str().unwrap_or("data").to_string(),
+        codec: stream["codec_name"].as_str().map(str::to_string),
+        width: stream["width"].as_u64().map(|width| width as u32),
+        height: stream["height"].as_u64().map(|height| height as u32),
+        frame_rate: frame_rate(&stream["avg_frame_rate"]).or_else(|| frame_rate(&stream["r_frame_rate"])),
+        sample_rate: number(&stream["sample_rate"]).map(|rate| rate as u32),
+        channels: stream["channels"].as_u64().map(|channels| channels as u32),
+    }).collect();
+
+    Ok(Some(StreamMetadata {
+        duration: number(&format["duration"]),
+        format: format["format_long_name"].as_str().or_else(|| format["format_name"].as_str()).map(str::to_string),
+        bit_rate: number(&format["bit_rate"]).map(|rate| rate as u64),
+        streams,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_reads_start_and_end() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range(" bytes=10-20, 30-40 ", 1000), Some((10, 20)));
+    }
+
+    #[test]
+    fn parse_range_clamps_to_the_file() {
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+    }
+
+    #[test]
+    fn parse_range_rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=20-10", 1000), None);
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+    }
+
+    #[test]
+    fn parse_range_rejects_malformed_headers() {
+        assert_eq!(parse_range("items=0-99", 1000), None);
+        assert_eq!(parse_range("bytes=abc", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+
+    #[test]
+    fn percent_decode_decodes_escapes() {
+        assert_eq!(percent_decode("My%20Videos/caf%C3%A9.mp4").as_deref(), Some("My Videos/café.mp4"));
+        assert_eq!(percent_decode("bad%2"), None);
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn parse_media_uri_keeps_paths_inside_the_user_directory() {
+        let expected = Some(("pi".to_string(), "Videos/clip.mp4".to_string()));
+        assert_eq!(parse_media_uri("pi://localhost/pi/Videos/clip.mp4"), expected);
+        assert_eq!(parse_media_uri("https://pi.localhost/pi/Videos/./clip.mp4?t=1"), expected);
+        assert_eq!(parse_media_uri("pi://localhost/pi/../other/clip.mp4"), None);
+        assert_eq!(parse_media_uri("pi://localhost/pi/%2E%2E/other/clip.mp4"), None);
+        assert_eq!(parse_media_uri("pi://localhost/pi/"), None);
+    }
+}
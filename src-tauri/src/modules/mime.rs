@@ -0,0 +1,204 @@
+use std::{collections::HashMap, io::Read, path::Path, sync::Mutex};
+
+use ssh2::{Session, Sftp};
+
+use super::{
+    file_info::{FileInfo, FileKind},
+    ssh_connection::{run_remote_command, shell_quote},
+};
+
+/// Cache of detected MIME types, keyed by remote path and invalidated when the modification time changes.
+#[derive(Default)]
+pub struct MimeCache(Mutex<CachedMimeTypes>);
+
+/// Cached MIME types with their modification time and when they were last used, evicting the least recently used.
+#[derive(Default)]
+struct CachedMimeTypes {
+    entries: HashMap<String, (String, String, u64)>,
+    clock: u64,
+}
+
+const SNIFF_SIZE: usize = 512;
+const FILE_COMMAND_BATCH_SIZE: usize = 200;
+const MAX_CACHED_MIME_TYPES: usize = 20000;
+
+/// Magic bytes at a given offset and the MIME type they identify.
+const MAGIC_SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"BM", "image/bmp"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\x00", "application/x-xz"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"\xff\xfb", "audio/mpeg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"OggS", "audio/ogg"),
+    (8, b"WAVE", "audio/wav"),
+    (8, b"AVI ", "video/x-msvideo"),
+    // ISO media files share the `ftyp` box, so images are told apart from videos by its major brand
+    (4, b"ftypheic", "image/heic"),
+    (4, b"ftypheix", "image/heic"),
+    (4, b"ftyphevc", "image/heic"),
+    (4, b"ftyphevx", "image/heic"),
+    (4, b"ftypmif1", "image/heif"),
+    (4, b"ftypmsf1", "image/heif"),
+    (4, b"ftypavif", "image/avif"),
+    (4, b"ftypavis", "image/avif"),
+    (4, b"ftypqt", "video/quicktime"),
+    (4, b"ftyp", "video/mp4"),
+    (0, b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+];
+
+/// Detects the MIME type of the regular files in a directory listing and stores it in `mime_type`.
+/// Uses `file --mime-type` on the Raspberry Pi when available, otherwise sniffs the first bytes of each file over SFTP.
+///
+/// * `Input`: SSH session, directory of the files, files to detect, and the MIME type cache
+/// * `Output`: None
+pub fn detect_mime_types(session: &mut Session, remote_dir: &str, files: &mut [FileInfo], cache: &MimeCache) -> Result<(), String> {
+    // The cache is only locked around lookups and inserts, so listings of other users are not held up by this one's I/O
+    let mut uncached = vec![];
+    {
+        let mut cached = cache.0.lock().map_err(|_| "MIME type cache is poisoned".to_string())?;
+        for (index, file) in files.iter_mut().enumerate() {
+            file.mime_type = match file.kind {
+                FileKind::Dir => Some("inode/directory".to_string()),
+                FileKind::Symlink => Some("inode/symlink".to_string()),
+                FileKind::Other => None,
+                FileKind::File => {
+                    let mime_type = cached.get(&format!("{}/{}", remote_dir, file.name), &file.last_modified);
+                    if mime_type.is_none() {
+                        uncached.push(index);
+                    }
+                    mime_type
+                }
+            };
+        }
+    }
+
+    for batch in uncached.chunks(FILE_COMMAND_BATCH_SIZE) {
+        let names: Vec<&str> = batch.iter().map(|&index| files[index].name.as_str()).collect();
+        let mime_types = match mime_types_with_file_command(session, remote_dir, &names)? {
+            Some(mime_types) => mime_types,
+            None => {
+                let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+                names.iter().map(|name| sniff_remote_file(&sftp, &format!("{}/{}", remote_dir, name))).collect()
+            }
+        };
+
+        let mut cached = cache.0.lock().map_err(|_| "MIME type cache is poisoned".to_string())?;
+        for (&index, mime_type) in batch.iter().zip(mime_types) {
+            let file = &mut files[index];
+            cached.insert(format!("{}/{}", remote_dir, file.name), file.last_modified.clone(), mime_type.clone());
+            file.mime_type = Some(mime_type);
+        }
+    }
+
+    Ok(())
+}
+
+impl CachedMimeTypes {
+    /// Gets the cached MIME type of a file, if it has not been modified since it was detected.
+    ///
+    /// * `Input`: Remote path and modification time of the file
+    /// * `Output`: MIME type, or None if it is not cached
+    fn get(&mut self, remote_path: &str, last_modified: &str) -> Option<String> {
+        self.clock += 1;
+        let clock = self.clock;
+        let (cached_last_modified, mime_type, last_used) = self.entries.get_mut(remote_path)?;
+        if cached_last_modified != last_modified {
+            return None;
+        }
+        *last_used = clock;
+        Some(mime_type.clone())
+    }
+
+    /// Caches the MIME type of a file, evicting the least recently used quarter of the cache when it is full.
+    ///
+    /// * `Input`: Remote path and modification time of the file, and its MIME type
+    fn insert(&mut self, remote_path: String, last_modified: String, mime_type: String) {
+        self.clock += 1;
+        self.entries.insert(remote_path, (last_modified, mime_type, self.clock));
+        if self.entries.len() > MAX_CACHED_MIME_TYPES {
+            let mut last_used: Vec<u64> = self.entries.values().map(|(_, _, last_used)| *last_used).collect();
+            last_used.sort_unstable();
+            let threshold = last_used[MAX_CACHED_MIME_TYPES / 4];
+            self.entries.retain(|_, (_, _, last_used)| *last_used >= threshold);
+        }
+    }
+}
+
+/// Detects the MIME type of file contents from their magic bytes.
+/// Contents without a known signature are reported as text if they are valid UTF-8 without NUL bytes.
+///
+/// * `Input`: First bytes of the file
+/// * `Output`: MIME type
+pub fn sniff_mime_type(bytes: &[u8]) -> &'static str {
+    for (offset, magic, mime_type) in MAGIC_SIGNATURES {
+        if bytes.len() >= offset + magic.len() && &bytes[*offset..offset + magic.len()] == *magic {
+            return mime_type;
+        }
+    }
+
+    if bytes.starts_with(b"#!") {
+        return "text/x-shellscript";
+    }
+    // The sniffed bytes may end in the middle of a multi-byte character
+    let text_prefix = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if text_prefix && !bytes.contains(&0) {
+        if bytes.is_empty() { "application/x-empty" } else { "text/plain" }
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Detects MIME types by running `file --mime-type` on the Raspberry Pi.
+///
+/// * `Input`: SSH session, directory of the files, and file names
+/// * `Output`: MIME type of each file in order, or None if `file` is unavailable or its output is unexpected
+fn mime_types_with_file_command(session: &mut Session, remote_dir: &str, names: &[&str]) -> Result<Option<Vec<String>>, String> {
+    let quoted_names: Vec<String> = names.iter().map(|name| shell_quote(name)).collect();
+    let command = format!(
+        "command -v file >/dev/null || exit 127; cd {} && file -b --mime-type -- {}",
+        shell_quote(remote_dir),
+        quoted_names.join(" ")
+    );
+    let (output, exit_status) = run_remote_command(session, &command)?;
+
+    let mime_types: Vec<String> = output.lines().map(|line| line.trim().to_string()).collect();
+    if exit_status != 0 || mime_types.len() != names.len() {
+        return Ok(None);
+    }
+    Ok(Some(mime_types))
+}
+
+/// Detects the MIME type of a remote file by reading its first bytes over SFTP.
+///
+/// * `Input`: SFTP session and remote file path
+/// * `Output`: MIME type, `application/octet-stream` if the file cannot be read
+fn sniff_remote_file(sftp: &Sftp, remote_file_path: &str) -> String {
+    let mut buffer = Vec::with_capacity(SNIFF_SIZE);
+    match sftp.open(Path::new(remote_file_path)) {
+        Ok(remote_file) => {
+            if remote_file.take(SNIFF_SIZE as u64).read_to_end(&mut buffer).is_err() {
+                return "application/octet-stream".to_string();
+            }
+        }
+        Err(_) => return "application/octet-stream".to_string(),
+    }
+    sniff_mime_type(&buffer).to_string()
+}
@@ -1 +1,22 @@
-pub mod ssh_connection;
\ No newline at end of file
+pub mod actions;
+pub mod duplicates;
+pub mod file_content;
+pub mod file_info;
+pub mod listing;
+pub mod media;
+pub mod mime;
+pub mod permissions;
+pub mod port_forwarding;
+pub mod search;
+pub mod services;
+pub mod ssh_connection;
+pub mod storage;
+pub mod symlinks;
+pub mod system_status;
+pub mod tail;
+pub mod terminal;
+pub mod thumbnails;
+pub mod trash;
+pub mod users;
+pub mod versions;
+pub mod watch;
\ No newline at end of file
@@ -0,0 +1,242 @@
+use std::path::Path;
+
+use ssh2::{FileStat, Sftp};
+use tauri::command;
+
+use super::{
+    file_info::OwnerNames,
+    ssh_connection::{get_remote_dirs_and_session, run_remote_command, shell_quote, validate_file_name},
+    users::require_admin,
+};
+
+//================================================================================================
+//                              Commands for permissions and ownership
+//================================================================================================
+
+/// Command to change the permissions of files or folders in the current directory.
+/// The mode is either octal (`755`) or symbolic (`u+x,go-w`, `a=r`), as accepted by `chmod`.
+///
+/// * `Input`: User's name, current path, file names, mode, and whether to apply it to folder contents
+/// * `Output`: None
+#[command]
+pub async fn set_permissions(user_name: String, current_path: Vec<String>, file_names: Vec<String>, mode: String, recursive: Option<bool>) -> Result<(), String> {
+    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    // Names are kept to entries of the current directory, which is already checked to be inside the user's directory
+    for file_name in &file_names {
+        validate_file_name(file_name)?;
+    }
+    for file_name in file_names {
+        let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+        for_each_path(&sftp, &remote_file_path, recursive.unwrap_or(false), &mut |path, stat| {
+            let current_mode = stat.perm.unwrap_or(0) & 0o7777;
+            let new_mode = parse_mode(&mode, current_mode, stat.is_dir())?;
+            set_stat(&sftp, path, FileStat { size: None, uid: None, gid: None, perm: Some(new_mode), atime: None, mtime: None })
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Command to change the owner and/or group of files or folders in the current directory.
+/// Restricted to admin users. Runs `chown` through `sudo -n`, since the Pi's user cannot give files away,
+/// so it needs passwordless sudo for it. Symlinks are changed themselves rather than their targets.
+///
+/// * `Input`: User's name, current path, file names, new owner and group names, and whether to apply them to folder contents
+/// * `Output`: None
+#[command]
+pub async fn chown(user_name: String, current_path: Vec<String>, file_names: Vec<String>, owner: Option<String>, group: Option<String>, recursive: Option<bool>) -> Result<(), String> {
+    require_admin(&user_name)?;
+    let (mut session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+
+    let quoted_paths = {
+        let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+        let owners = OwnerNames::load(&sftp);
+        if let Some(owner) = &owner {
+            owners.user_id(owner).ok_or_else(|| format!("Unknown user '{}'", owner))?;
+        }
+        if let Some(group) = &group {
+            owners.group_id(group).ok_or_else(|| format!("Unknown group '{}'", group))?;
+        }
+
+        let mut quoted_paths = vec![];
+        // Names are kept to entries of the current directory, which is already checked to be inside the user's directory
+        for file_name in &file_names {
+            validate_file_name(file_name)?;
+            let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+            sftp.lstat(Path::new(&remote_file_path)).map_err(|e| format!("Failed to stat '{}': {}", remote_file_path, e))?;
+            quoted_paths.push(shell_quote(&remote_file_path));
+        }
+        quoted_paths
+    };
+
+    let owner_spec = match (&owner, &group) {
+        (Some(owner), Some(group)) => format!("{}:{}", owner, group),
+        (Some(owner), None) => owner.clone(),
+        (None, Some(group)) => format!(":{}", group),
+        (None, None) => return Ok(()),
+    };
+    if quoted_paths.is_empty() {
+        return Ok(());
+    }
+
+    let command = format!(
+        "sudo -n chown -h {}-- {} {} 2>&1",
+        if recursive.unwrap_or(false) { "-R " } else { "" },
+        shell_quote(&owner_spec),
+        quoted_paths.join(" "),
+    );
+    let (output, exit_status) = run_remote_command(&mut session, &command)?;
+    if exit_status != 0 {
+        return Err(format!("Failed to change owner: {}", output.trim()));
+    }
+
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for permissions and ownership
+//================================================================================================
+
+/// Computes a new mode from an octal or symbolic mode string, as `chmod` does.
+///
+/// * `Input`: Mode string, current permission bits, and whether the path is a directory
+/// * `Output`: New permission bits
+pub fn parse_mode(mode: &str, current_mode: u32, is_dir: bool) -> Result<u32, String> {
+    let mode = mode.trim();
+    if !mode.is_empty() && mode.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .ok_or_else(|| format!("Invalid mode '{}'", mode));
+    }
+
+    let invalid = || format!("Invalid mode '{}'", mode);
+    let mut new_mode = current_mode;
+    for clause in mode.split(',') {
+        let who_end = clause.find(['+', '-', '=']).ok_or_else(invalid)?;
+        let (who, mut rest) = clause.split_at(who_end);
+
+        // Bits each permission letter applies to, for the selected classes
+        let mut class_mask = 0;
+        for c in who.chars() {
+            class_mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => return Err(invalid()),
+            };
+        }
+        if class_mask == 0 {
+            class_mask = 0o7777;
+        }
+
+        while let Some(op) = rest.chars().next() {
+            let perms_end = rest[1..].find(['+', '-', '=']).map_or(rest.len(), |i| i + 1);
+            let perms = &rest[1..perms_end];
+            rest = &rest[perms_end..];
+
+            let mut bits = 0;
+            for c in perms.chars() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    'X' if is_dir || new_mode & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    _ => return Err(invalid()),
+                };
+            }
+            bits &= class_mask;
+
+            new_mode = match op {
+                '+' => new_mode | bits,
+                '-' => new_mode & !bits,
+                '=' => (new_mode & !class_mask) | bits,
+                _ => return Err(invalid()),
+            };
+        }
+    }
+
+    Ok(new_mode)
+}
+
+/// Calls a function for a path and, if requested, everything inside it.
+/// Symlinks are skipped, since changing their attributes over SFTP would change their target instead.
+///
+/// * `Input`: SFTP session, remote path, whether to recurse into folders, and the function to call
+/// * `Output`: None
+fn for_each_path(sftp: &Sftp, remote_path: &str, recursive: bool, action: &mut dyn FnMut(&Path, &FileStat) -> Result<(), String>) -> Result<(), String> {
+    let path = Path::new(remote_path);
+    let stat = sftp.lstat(path).map_err(|e| format!("Failed to stat '{}': {}", remote_path, e))?;
+    if stat.file_type().is_symlink() {
+        return Ok(());
+    }
+    action(path, &stat)?;
+
+    if recursive && stat.is_dir() {
+        let entries = sftp.readdir(path).map_err(|e| format!("Failed to read directory '{}': {}", remote_path, e))?;
+        for (entry_path, _) in entries {
+            for_each_path(sftp, &entry_path.to_string_lossy(), recursive, action)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies the set fields of a stat to a remote path.
+///
+/// * `Input`: SFTP session, remote path, and stat to apply
+/// * `Output`: None
+fn set_stat(sftp: &Sftp, path: &Path, stat: FileStat) -> Result<(), String> {
+    sftp.setstat(path, stat).map_err(|e| format!("Failed to change attributes of '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mode_accepts_octal_modes() {
+        assert_eq!(parse_mode("755", 0o644, false), Ok(0o755));
+        assert_eq!(parse_mode("0640", 0o777, false), Ok(0o640));
+        assert_eq!(parse_mode(" 4755 ", 0, false), Ok(0o4755));
+        assert!(parse_mode("17777", 0, false).is_err());
+    }
+
+    #[test]
+    fn parse_mode_applies_symbolic_clauses() {
+        assert_eq!(parse_mode("u+x", 0o644, false), Ok(0o744));
+        assert_eq!(parse_mode("go-w", 0o666, false), Ok(0o644));
+        assert_eq!(parse_mode("a=r", 0o755, false), Ok(0o444));
+        assert_eq!(parse_mode("+x", 0o644, false), Ok(0o755));
+        assert_eq!(parse_mode("u+rw,g=r,o=", 0o000, false), Ok(0o640));
+        assert_eq!(parse_mode("u+x-w", 0o644, false), Ok(0o544));
+    }
+
+    #[test]
+    fn parse_mode_sets_special_bits_for_the_selected_classes() {
+        assert_eq!(parse_mode("u+s", 0o755, false), Ok(0o4755));
+        assert_eq!(parse_mode("g+s", 0o755, true), Ok(0o2755));
+        assert_eq!(parse_mode("+t", 0o777, true), Ok(0o1777));
+    }
+
+    #[test]
+    fn parse_mode_only_adds_conditional_execute_to_directories_or_executables() {
+        assert_eq!(parse_mode("a+X", 0o644, false), Ok(0o644));
+        assert_eq!(parse_mode("a+X", 0o644, true), Ok(0o755));
+        assert_eq!(parse_mode("a+X", 0o744, false), Ok(0o755));
+    }
+
+    #[test]
+    fn parse_mode_rejects_invalid_modes() {
+        assert!(parse_mode("", 0o644, false).is_err());
+        assert!(parse_mode("u", 0o644, false).is_err());
+        assert!(parse_mode("z+x", 0o644, false).is_err());
+        assert!(parse_mode("u+q", 0o644, false).is_err());
+        assert!(parse_mode("789", 0o644, false).is_err());
+    }
+}
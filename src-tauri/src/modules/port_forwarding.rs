@@ -0,0 +1,350 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{ErrorKind, Read, Write},
+    net::{IpAddr, Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use ssh2::{Channel, ErrorCode, Session};
+use tauri::{command, AppHandle, Manager, State};
+
+use super::{ssh_connection::get_ssh_session, users::find_user};
+
+/// Open port forwards, shared between the commands and the threads driving them.
+#[derive(Default)]
+pub struct PortForwards(Mutex<OpenForwards>);
+
+/// Open port forwards, keyed by ID.
+#[derive(Default)]
+struct OpenForwards {
+    last_id: u32,
+    forwards: HashMap<u32, OpenForward>,
+}
+
+/// Port forward opened by a user, with the counters updated by its thread and the sender stopping it.
+struct OpenForward {
+    owner: String,
+    local_port: u16,
+    remote_host: String,
+    remote_port: u16,
+    counters: Arc<ForwardCounters>,
+    sender: Sender<()>,
+}
+
+/// Traffic through a port forward.
+#[derive(Default)]
+struct ForwardCounters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    connections: AtomicUsize,
+}
+
+/// Connection accepted on the local port, relayed through a channel to the Pi.
+struct ForwardedConnection {
+    stream: TcpStream,
+    channel: Channel,
+    /// Data read from the local connection, not yet written to the channel
+    to_remote: Vec<u8>,
+    /// Data read from the channel, not yet written to the local connection
+    to_local: Vec<u8>,
+    local_closed: bool,
+    eof_sent: bool,
+}
+
+/// Struct to represent a port forward and its traffic.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortForward {
+    pub id: u32,
+    pub local_port: u16,
+    pub remote_host: String,
+    pub remote_port: u16,
+    /// Bytes sent from the local port to the Pi
+    pub bytes_sent: u64,
+    /// Bytes received from the Pi
+    pub bytes_received: u64,
+    /// Connections currently open through the forward
+    pub connections: usize,
+}
+
+/// Struct to represent the end of a port forward, emitted as a "port-forward-end" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortForwardEnd {
+    pub id: u32,
+    /// Error that closed the forward, or None if it was closed by the user
+    pub error: Option<String>,
+}
+
+/// Struct to represent a connection the Pi could not open for a port forward, emitted as a "port-forward-error" event.
+/// The forward stays open for the next connection.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortForwardError {
+    pub id: u32,
+    pub error: String,
+}
+
+const DEFAULT_REMOTE_HOST: &str = "localhost";
+const LOCAL_HOST: &str = "127.0.0.1";
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Reading from either side pauses while this much data waits to be written to the other side
+const MAX_PENDING_SIZE: usize = 256 * 1024;
+/// Error code of libssh2 for an operation that would block in non-blocking mode
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+
+//================================================================================================
+//                              Commands for port forwarding
+//================================================================================================
+
+/// Command to forward a local port to a port on the Raspberry Pi through the SSH connection.
+/// The local port only accepts connections from this computer. Only admin users may forward to hosts other than the Pi itself;
+/// forwards of other users always connect to 127.0.0.1 on the Pi, whatever loopback name they were given.
+/// A connection the Pi cannot open emits a "port-forward-error" event, and a "port-forward-end" event is emitted when the forward closes.
+///
+/// * `Input`: User's name, port on the Pi, host to reach from the Pi (default localhost), local port (default any free port), app handle for emitting events, and the open port forwards
+/// * `Output`: The new port forward
+#[command]
+pub async fn open_port_forward(user_name: String, remote_port: u16, remote_host: Option<String>, local_port: Option<u16>, app_handle: AppHandle, forwards: State<'_, PortForwards>) -> Result<PortForward, String> {
+    let user = find_user(&user_name)?;
+    let remote_host = remote_host.unwrap_or_else(|| DEFAULT_REMOTE_HOST.to_string());
+    let remote_host = if user.is_admin() {
+        remote_host
+    } else if is_local_host(&remote_host) {
+        // Names are resolved on the Pi, so only a fixed loopback address keeps the forward on the Pi itself
+        LOCAL_HOST.to_string()
+    } else {
+        return Err(format!("User '{}' may only forward ports of the Raspberry Pi itself", user_name));
+    };
+
+    let session = get_ssh_session().await?;
+    let listener = TcpListener::bind((LOCAL_HOST, local_port.unwrap_or(0)))
+        .map_err(|e| format!("Failed to listen on local port {}: {}", local_port.unwrap_or(0), e))?;
+    listener.set_nonblocking(true).map_err(|e| format!("Failed to configure local port: {}", e))?;
+    let local_port = listener.local_addr().map_err(|e| format!("Failed to get local port: {}", e))?.port();
+
+    let (sender, receiver) = mpsc::channel();
+    let counters = Arc::new(ForwardCounters::default());
+    let forward = OpenForward {
+        owner: user_name.to_lowercase(),
+        local_port,
+        remote_host: remote_host.clone(),
+        remote_port,
+        counters: counters.clone(),
+        sender,
+    };
+    let id = {
+        let mut forwards = forwards.0.lock().map_err(|_| "Port forwards are poisoned".to_string())?;
+        forwards.last_id += 1;
+        let id = forwards.last_id;
+        forwards.forwards.insert(id, forward);
+        id
+    };
+
+    let target = (remote_host.clone(), remote_port);
+    thread::spawn(move || {
+        let error = run_port_forward(id, &session, &listener, &target, &counters, receiver, &app_handle).err();
+        if let Ok(mut forwards) = app_handle.state::<PortForwards>().0.lock() {
+            forwards.forwards.remove(&id);
+        }
+        app_handle.emit_all("port-forward-end", PortForwardEnd { id, error }).unwrap();
+    });
+
+    Ok(PortForward { id, local_port, remote_host, remote_port, bytes_sent: 0, bytes_received: 0, connections: 0 })
+}
+
+/// Command to close a port forward, along with the connections open through it.
+///
+/// * `Input`: User's name, ID of the port forward, and the open port forwards
+/// * `Output`: None
+#[command]
+pub async fn close_port_forward(user_name: String, id: u32, forwards: State<'_, PortForwards>) -> Result<(), String> {
+    let mut forwards = forwards.0.lock().map_err(|_| "Port forwards are poisoned".to_string())?;
+    let is_owner = forwards.forwards.get(&id).map(|forward| forward.owner.eq_ignore_ascii_case(&user_name)).unwrap_or(false);
+    if !is_owner {
+        return Err(format!("Port forward {} is not open", id));
+    }
+    if let Some(forward) = forwards.forwards.remove(&id) {
+        forward.sender.send(()).ok();
+    }
+    Ok(())
+}
+
+/// Command to list the user's open port forwards with their traffic.
+///
+/// * `Input`: User's name and the open port forwards
+/// * `Output`: Port forwards, oldest first
+#[command]
+pub async fn list_port_forwards(user_name: String, forwards: State<'_, PortForwards>) -> Result<Vec<PortForward>, String> {
+    let forwards = forwards.0.lock().map_err(|_| "Port forwards are poisoned".to_string())?;
+    let mut port_forwards: Vec<PortForward> = forwards.forwards
+        .iter()
+        .filter(|(_, forward)| forward.owner.eq_ignore_ascii_case(&user_name))
+        .map(|(id, forward)| PortForward {
+            id: *id,
+            local_port: forward.local_port,
+            remote_host: forward.remote_host.clone(),
+            remote_port: forward.remote_port,
+            bytes_sent: forward.counters.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: forward.counters.bytes_received.load(Ordering::Relaxed),
+            connections: forward.counters.connections.load(Ordering::Relaxed),
+        })
+        .collect();
+    port_forwards.sort_by_key(|forward| forward.id);
+    Ok(port_forwards)
+}
+
+//================================================================================================
+//                              Helper functions for port forwarding
+//================================================================================================
+
+/// Accepts connections on the local port and relays each one through a `direct-tcpip` channel until the forward is closed.
+/// A single thread drives every connection, since channels of a session cannot block independently of each other,
+/// so the session is kept in non-blocking mode. Channels are opened one at a time, as libssh2 tracks a single pending open per session,
+/// and accepted connections wait their turn without holding up the ones already relayed.
+///
+/// * `Input`: Port forward ID, SSH session, local listener, host and port to reach from the Pi, traffic counters, stop receiver, and app handle for emitting events
+/// * `Output`: None, or an error if the forward could not keep running
+fn run_port_forward(id: u32, session: &Session, listener: &TcpListener, target: &(String, u16), counters: &ForwardCounters, receiver: Receiver<()>, app_handle: &AppHandle) -> Result<(), String> {
+    let mut connections: Vec<ForwardedConnection> = vec![];
+    let mut waiting: VecDeque<TcpStream> = VecDeque::new();
+    session.set_blocking(false);
+    let result = loop {
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            Ok(()) | Err(TryRecvError::Disconnected) => break Ok(()),
+        }
+
+        let mut idle = true;
+        match listener.accept() {
+            Ok((stream, _)) => {
+                idle = false;
+                waiting.push_back(stream);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => break Err(format!("Failed to accept connection: {}", e)),
+        }
+
+        if !waiting.is_empty() {
+            // A connection the Pi refuses is dropped, leaving the forward open for the next one
+            match session.channel_direct_tcpip(&target.0, target.1, None) {
+                Ok(channel) => {
+                    idle = false;
+                    if let Some(stream) = waiting.pop_front().filter(|stream| stream.set_nonblocking(true).is_ok()) {
+                        connections.push(ForwardedConnection { stream, channel, to_remote: vec![], to_local: vec![], local_closed: false, eof_sent: false });
+                    }
+                }
+                Err(e) if e.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => {}
+                Err(e) => {
+                    idle = false;
+                    waiting.pop_front();
+                    let error = format!("Failed to connect to {}:{} from the Pi: {}", target.0, target.1, e);
+                    app_handle.emit_all("port-forward-error", PortForwardError { id, error }).unwrap();
+                }
+            }
+        }
+
+        let mut index = 0;
+        while index < connections.len() {
+            match relay_connection(&mut connections[index], counters) {
+                Ok(Some(progressed)) => {
+                    idle &= !progressed;
+                    index += 1;
+                }
+                Ok(None) | Err(_) => {
+                    let mut connection = connections.swap_remove(index);
+                    connection.stream.shutdown(Shutdown::Both).ok();
+                    connection.channel.close().ok();
+                }
+            }
+        }
+        counters.connections.store(connections.len(), Ordering::Relaxed);
+
+        if idle {
+            thread::sleep(POLL_INTERVAL);
+        }
+    };
+
+    session.set_blocking(true);
+    for mut connection in connections {
+        connection.stream.shutdown(Shutdown::Both).ok();
+        connection.channel.close().ok();
+    }
+    counters.connections.store(0, Ordering::Relaxed);
+    result
+}
+
+/// Moves the data available on either side of a connection to the other side, without blocking.
+///
+/// * `Input`: Connection and traffic counters
+/// * `Output`: Whether any data moved, or None once the Pi has closed its side and everything was written
+fn relay_connection(connection: &mut ForwardedConnection, counters: &ForwardCounters) -> std::io::Result<Option<bool>> {
+    let mut buffer = [0; 16384];
+    let mut progressed = false;
+
+    if !connection.local_closed && connection.to_remote.len() < MAX_PENDING_SIZE {
+        match connection.stream.read(&mut buffer) {
+            Ok(0) => connection.local_closed = true,
+            Ok(read) => {
+                progressed = true;
+                connection.to_remote.extend_from_slice(&buffer[..read]);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if !connection.to_remote.is_empty() {
+        match connection.channel.write(&connection.to_remote) {
+            Ok(written) => {
+                progressed |= written > 0;
+                connection.to_remote.drain(..written);
+                counters.bytes_sent.fetch_add(written as u64, Ordering::Relaxed);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if connection.local_closed && connection.to_remote.is_empty() && !connection.eof_sent && connection.channel.send_eof().is_ok() {
+        connection.eof_sent = true;
+    }
+
+    if connection.to_local.len() < MAX_PENDING_SIZE {
+        match connection.channel.read(&mut buffer) {
+            Ok(0) => {}
+            Ok(read) => {
+                progressed = true;
+                connection.to_local.extend_from_slice(&buffer[..read]);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if !connection.to_local.is_empty() {
+        match connection.stream.write(&connection.to_local) {
+            Ok(written) => {
+                progressed |= written > 0;
+                connection.to_local.drain(..written);
+                counters.bytes_received.fetch_add(written as u64, Ordering::Relaxed);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if connection.channel.eof() && connection.to_local.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(progressed))
+}
+
+/// Checks whether a host names the Raspberry Pi itself.
+///
+/// * `Input`: Host name or address
+/// * `Output`: Whether the host is `localhost` or a loopback address
+fn is_local_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().map(|address| address.is_loopback()).unwrap_or(false)
+}
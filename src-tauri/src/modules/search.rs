@@ -0,0 +1,416 @@
+use std::{io::Read, path::Path};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use ssh2::{Session, Sftp};
+use tauri::{command, AppHandle, Manager};
+
+use super::{
+    file_info::{file_info_from_stat, FileInfo, OwnerNames},
+    ssh_connection::{get_remote_dirs_and_session, run_remote_command, shell_quote},
+};
+
+/// Struct to represent a file found by a search, with its path relative to the user's directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub path: String,
+    pub file: FileInfo,
+}
+
+/// Optional filters applied to the files found by a search.
+/// Sizes are in bytes and dates are in epoch seconds.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchFilters {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<u64>,
+    pub modified_before: Option<u64>,
+    /// Either "file" or "folder"
+    pub kind: Option<String>,
+}
+
+/// Struct to represent a line of a file that matched a content search.
+#[derive(Debug, Serialize)]
+pub struct GrepMatch {
+    pub path: String,
+    pub line_number: u64,
+    pub line: String,
+}
+
+const MAX_GREP_MATCHES: usize = 1000;
+const MAX_SNIPPET_LENGTH: usize = 200;
+const MAX_FALLBACK_FILE_SIZE: u64 = 10 * 1024 * 1024; // 10MB
+const BINARY_SNIFF_SIZE: usize = 8 * 1024; // 8KB
+
+//================================================================================================
+//                              Commands for file search
+//================================================================================================
+
+/// Command to recursively search the user's directory for files matching a name pattern.
+/// Patterns containing `*` or `?` are treated as globs, otherwise as a substring. Matching is case-insensitive.
+/// Each result is emitted as a `search-result` event as soon as it is found.
+///
+/// * `Input`: User's name, name pattern, optional filters, and app handle for emitting events
+/// * `Output`: List of all matching files
+#[command]
+pub async fn search_files(user_name: String, pattern: String, filters: Option<SearchFilters>, app_handle: AppHandle) -> Result<Vec<SearchResult>, String> {
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, vec![]).await?;
+    let filters = filters.unwrap_or_default();
+    let pattern = pattern.to_lowercase();
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let owners = OwnerNames::load(&sftp);
+
+    let mut results = vec![];
+    let mut pending_dirs = vec![String::new()];
+    while let Some(relative_dir) = pending_dirs.pop() {
+        let dir = if relative_dir.is_empty() {
+            remote_dir.clone()
+        } else {
+            format!("{}/{}", remote_dir, relative_dir)
+        };
+        // Subdirectories that cannot be read are skipped rather than failing the whole search
+        let entries = match sftp.readdir(Path::new(&dir)) {
+            Ok(entries) => entries,
+            Err(_) if !relative_dir.is_empty() => continue,
+            Err(e) => return Err(format!("Failed to read directory {}: {}", dir, e)),
+        };
+
+        for (path, stat) in entries {
+            let file = match file_info_from_stat(&sftp, &path, &stat, &owners) {
+                Some(file) => file,
+                None => continue,
+            };
+            let relative_path = if relative_dir.is_empty() {
+                file.name.clone()
+            } else {
+                format!("{}/{}", relative_dir, file.name)
+            };
+            if stat.is_dir() {
+                pending_dirs.push(relative_path.clone());
+            }
+
+            if name_matches(&file.name.to_lowercase(), &pattern) && passes_filters(&stat, &filters) {
+                let result = SearchResult { path: relative_path, file };
+                app_handle.emit_all("search-result", result.clone()).unwrap();
+                results.push(result);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Command to search the contents of the files in the user's directory.
+/// Runs `grep` on the Raspberry Pi, falling back to reading the files over SFTP if `grep` is unavailable.
+/// Binary files are skipped and at most 1000 matches are returned.
+/// Regex patterns should use POSIX extended syntax (`grep -E`), which both paths accept; the SFTP fallback uses the `regex` crate,
+/// so back-references only work through `grep`, and extensions such as `\d` or lazy quantifiers only through the fallback.
+///
+/// * `Input`: User's name, search pattern, whether the pattern is a regex, and whether to ignore case
+/// * `Output`: List of matching lines with their file path and line number
+#[command]
+pub async fn grep_files(user_name: String, pattern: String, is_regex: bool, ignore_case: bool) -> Result<Vec<GrepMatch>, String> {
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name, vec![]).await?;
+
+    // Also used to find the match in lines from grep, which may accept patterns the regex crate does not
+    let regex = RegexBuilder::new(&if is_regex { pattern.clone() } else { regex::escape(&pattern) })
+        .case_insensitive(ignore_case)
+        .build();
+    if let Some(matches) = grep_with_exec(&mut session, &remote_dir, &pattern, is_regex, ignore_case, regex.as_ref().ok())? {
+        return Ok(matches);
+    }
+
+    let regex = regex.map_err(|e| format!("Invalid search pattern '{}': {}", pattern, e))?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    grep_with_sftp(&sftp, &remote_dir, &regex)
+}
+
+//================================================================================================
+//                              Helper functions for file search
+//================================================================================================
+
+/// Searches file contents by running `grep` on the Raspberry Pi.
+/// `grep` exits with 0 when lines matched, 1 when nothing matched, and 2 on errors such as unreadable files.
+/// The output is capped on the Pi, so only the first 1000 matches are sent back, while the exit status is still that of `grep`.
+///
+/// * `Input`: SSH session, user's directory, search pattern, whether it is a regex, whether to ignore case, and the pattern compiled to locate matches, if it compiles
+/// * `Output`: List of matches, or None if `grep` is not available
+fn grep_with_exec(session: &mut Session, remote_dir: &str, pattern: &str, is_regex: bool, ignore_case: bool, regex: Option<&Regex>) -> Result<Option<Vec<GrepMatch>>, String> {
+    // -r recursive, -n line numbers, -I skip binary files, -Z separate the file name with a NUL byte
+    let mut flags = String::from("-rnIZ");
+    flags.push_str(if is_regex { "E" } else { "F" });
+    if ignore_case {
+        flags.push('i');
+    }
+    // The exit status of grep is passed out of the pipe through file descriptor 3, while head writes to the original output on 4
+    let command = format!(
+        "command -v grep >/dev/null || exit 127; exec 4>&1; status=$( {{ {{ grep {} -m {} -e {} -- {} 2>/dev/null; echo $? >&3; }} | head -n {} >&4; }} 3>&1 ); exit $status",
+        flags, MAX_GREP_MATCHES, shell_quote(pattern), shell_quote(remote_dir), MAX_GREP_MATCHES,
+    );
+    let (output, exit_status) = run_remote_command(session, &command)?;
+    if exit_status == 127 {
+        return Ok(None);
+    }
+    if output.is_empty() && exit_status > 1 {
+        return Err(format!("Failed to search file contents: grep exited with status {}", exit_status));
+    }
+
+    let mut matches = vec![];
+    for line in output.lines().take(MAX_GREP_MATCHES) {
+        // Each line is `<path>\0<line number>:<content>`
+        let (path, rest) = match line.split_once('\0') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let (line_number, content) = match rest.split_once(':') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let line_number = match line_number.parse() {
+            Ok(line_number) => line_number,
+            Err(_) => continue,
+        };
+        matches.push(GrepMatch {
+            path: relative_to(path, remote_dir),
+            line_number,
+            line: make_snippet(content, regex),
+        });
+    }
+
+    Ok(Some(matches))
+}
+
+/// Searches file contents by reading every file in the user's directory over SFTP.
+/// Files larger than 10MB and files containing NUL bytes near the start are skipped.
+///
+/// * `Input`: SFTP session, user's directory, and compiled search pattern
+/// * `Output`: List of matches
+fn grep_with_sftp(sftp: &Sftp, remote_dir: &str, regex: &Regex) -> Result<Vec<GrepMatch>, String> {
+    let mut matches = vec![];
+    let mut pending_dirs = vec![remote_dir.to_string()];
+    while let Some(dir) = pending_dirs.pop() {
+        // Subdirectories that cannot be read are skipped rather than failing the whole search
+        let entries = match sftp.readdir(Path::new(&dir)) {
+            Ok(entries) => entries,
+            Err(_) if dir != remote_dir => continue,
+            Err(e) => return Err(format!("Failed to read directory {}: {}", dir, e)),
+        };
+        for (path, stat) in entries {
+            if stat.is_dir() {
+                pending_dirs.push(path.to_string_lossy().to_string());
+                continue;
+            }
+            if !stat.is_file() || stat.size.unwrap_or(0) > MAX_FALLBACK_FILE_SIZE {
+                continue;
+            }
+
+            // Skip files that cannot be opened rather than failing the whole search
+            let mut contents = vec![];
+            match sftp.open(&path) {
+                Ok(mut remote_file) => {
+                    if remote_file.read_to_end(&mut contents).is_err() {
+                        continue;
+                    }
+                }
+                Err(_) => continue,
+            }
+            if contents[..contents.len().min(BINARY_SNIFF_SIZE)].contains(&0) {
+                continue;
+            }
+
+            let relative_path = relative_to(&path.to_string_lossy(), remote_dir);
+            for (index, line) in String::from_utf8_lossy(&contents).lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(GrepMatch {
+                        path: relative_path.clone(),
+                        line_number: index as u64 + 1,
+                        line: make_snippet(line, Some(regex)),
+                    });
+                    if matches.len() >= MAX_GREP_MATCHES {
+                        return Ok(matches);
+                    }
+                }
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Strips the user's directory from the start of a remote path.
+///
+/// * `Input`: Remote path and user's directory
+/// * `Output`: Path relative to the user's directory
+fn relative_to(path: &str, remote_dir: &str) -> String {
+    path.strip_prefix(remote_dir)
+        .map(|relative| relative.trim_start_matches('/'))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Trims a matching line and shortens it to at most 200 characters around the match, marking cut ends with `...`.
+///
+/// * `Input`: Matching line and the pattern to locate the match with, if any
+/// * `Output`: Line snippet
+fn make_snippet(line: &str, regex: Option<&Regex>) -> String {
+    let line = line.trim();
+    let length = line.chars().count();
+    if length <= MAX_SNIPPET_LENGTH {
+        return line.to_string();
+    }
+
+    // Centre the window on the match, in characters, keeping its start when the match is longer than the window
+    let start = match regex.and_then(|regex| regex.find(line)) {
+        Some(found) => {
+            let match_start = line[..found.start()].chars().count();
+            let match_length = line[found.start()..found.end()].chars().count();
+            let centred = (match_start + match_length / 2).saturating_sub(MAX_SNIPPET_LENGTH / 2);
+            centred.min(match_start).min(length - MAX_SNIPPET_LENGTH)
+        }
+        None => 0,
+    };
+    let end = start + MAX_SNIPPET_LENGTH;
+
+    let snippet: String = line.chars().skip(start).take(MAX_SNIPPET_LENGTH).collect();
+    format!("{}{}{}", if start > 0 { "..." } else { "" }, snippet, if end < length { "..." } else { "" })
+}
+
+/// Checks whether a file name matches a search pattern.
+/// Both are expected to already be lowercase.
+///
+/// * `Input`: File name and pattern
+/// * `Output`: True if the name matches
+fn name_matches(name: &str, pattern: &str) -> bool {
+    if pattern.contains('*') || pattern.contains('?') {
+        let name: Vec<char> = name.chars().collect();
+        let pattern: Vec<char> = pattern.chars().collect();
+        glob_matches(&name, &pattern)
+    } else {
+        name.contains(pattern)
+    }
+}
+
+/// Matches a name against a glob pattern supporting `*` and `?` wildcards.
+///
+/// * `Input`: Name and pattern characters
+/// * `Output`: True if the whole name matches the pattern
+fn glob_matches(name: &[char], pattern: &[char]) -> bool {
+    let (mut n, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            n += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            // Let the last `*` absorb one more character and retry
+            backtrack = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Checks whether a file passes the size, date and kind filters of a search.
+///
+/// * `Input`: File stat and search filters
+/// * `Output`: True if the file passes every filter that is set
+fn passes_filters(stat: &ssh2::FileStat, filters: &SearchFilters) -> bool {
+    let size = stat.size.unwrap_or(0);
+    let modified = stat.mtime.unwrap_or(0);
+    let kind_matches = match filters.kind.as_deref() {
+        Some("file") => stat.is_file(),
+        Some("folder") => stat.is_dir(),
+        _ => true,
+    };
+
+    kind_matches
+        && filters.min_size.map_or(true, |min| size >= min)
+        && filters.max_size.map_or(true, |max| size <= max)
+        && filters.modified_after.map_or(true, |after| modified >= after)
+        && filters.modified_before.map_or(true, |before| modified <= before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(name: &str, pattern: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        let pattern: Vec<char> = pattern.chars().collect();
+        glob_matches(&name, &pattern)
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob("photo.jpg", "*.jpg"));
+        assert!(glob("photo.jpg", "photo.*"));
+        assert!(glob("photo.jpg", "ph?to.jpg"));
+        assert!(glob("photo.jpg", "*"));
+        assert!(glob("a.b.c.txt", "*.*.txt"));
+        assert!(glob("", "*"));
+    }
+
+    #[test]
+    fn glob_matches_the_whole_name() {
+        assert!(!glob("photo.jpg.bak", "*.jpg"));
+        assert!(!glob("photo.jpg", "?.jpg"));
+        assert!(!glob("photo.jpg", "photo"));
+        assert!(!glob("", "?"));
+    }
+
+    #[test]
+    fn glob_matches_backtracks_over_repeated_characters() {
+        assert!(glob("aaab", "*ab"));
+        assert!(glob("mississippi", "m*iss*ppi"));
+        assert!(!glob("mississippi", "m*iss*ppx"));
+    }
+
+    #[test]
+    fn name_matches_uses_a_substring_without_wildcards() {
+        assert!(name_matches("holiday photo.jpg", "photo"));
+        assert!(!name_matches("holiday photo.jpg", "photo*"));
+    }
+
+    #[test]
+    fn make_snippet_keeps_short_lines_whole() {
+        assert_eq!(make_snippet("   let x = 1;  ", None), "let x = 1;");
+    }
+
+    #[test]
+    fn make_snippet_centres_long_lines_on_the_match() {
+        let line = format!("{}needle{}", "a".repeat(500), "b".repeat(500));
+        let regex = Regex::new("needle").unwrap();
+        let snippet = make_snippet(&line, Some(&regex));
+        assert!(snippet.starts_with("...") && snippet.ends_with("..."));
+        assert_eq!(snippet.chars().count(), MAX_SNIPPET_LENGTH + 6);
+        let needle = snippet.find("needle").unwrap();
+        assert!((needle as isize - 100).abs() <= 3);
+    }
+
+    #[test]
+    fn make_snippet_only_marks_cut_ends() {
+        let line = format!("needle{}", "b".repeat(500));
+        let regex = Regex::new("needle").unwrap();
+        let snippet = make_snippet(&line, Some(&regex));
+        assert!(snippet.starts_with("needle") && snippet.ends_with("..."));
+
+        let line = format!("{}needle", "a".repeat(500));
+        let snippet = make_snippet(&line, Some(&regex));
+        assert!(snippet.starts_with("...") && snippet.ends_with("needle"));
+    }
+
+    #[test]
+    fn make_snippet_counts_characters_rather_than_bytes() {
+        let line = format!("{}needle{}", "\u{e9}".repeat(500), "\u{e9}".repeat(500));
+        let regex = Regex::new("needle").unwrap();
+        let snippet = make_snippet(&line, Some(&regex));
+        assert_eq!(snippet.chars().count(), MAX_SNIPPET_LENGTH + 6);
+        assert!(snippet.contains("needle"));
+    }
+}
@@ -0,0 +1,174 @@
+use std::env;
+
+use serde::Serialize;
+use ssh2::Session;
+use tauri::command;
+
+use super::{
+    ssh_connection::{get_ssh_session, run_remote_command, shell_quote},
+    users::require_admin,
+};
+
+/// Struct to represent a systemd unit and its state, as reported by `systemctl show`.
+#[derive(Debug, Serialize)]
+pub struct ServiceUnit {
+    pub name: String,
+    pub description: String,
+    /// e.g. "loaded" or "not-found"
+    pub load_state: String,
+    /// e.g. "active", "inactive" or "failed"
+    pub active_state: String,
+    /// e.g. "running" or "dead"
+    pub sub_state: String,
+    /// e.g. "enabled" or "disabled", empty for units without a unit file
+    pub unit_file_state: String,
+}
+
+const SERVICE_ACTIONS: &[&str] = &["start", "stop", "restart", "enable", "disable"];
+const DEFAULT_LOG_LINES: u32 = 100;
+const MAX_LOG_LINES: u32 = 5000;
+
+//================================================================================================
+//                              Commands for services
+//================================================================================================
+
+/// Command to list the systemd units in `VITE_SERVICES` with their state. Restricted to admin users.
+///
+/// * `Input`: User's name
+/// * `Output`: Allowed units, in the order they are configured
+#[command]
+pub async fn list_services(user_name: String) -> Result<Vec<ServiceUnit>, String> {
+    require_admin(&user_name)?;
+    let units = load_allowed_services()?;
+    if units.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut session = get_ssh_session().await?;
+    get_service_units(&mut session, &units)
+}
+
+/// Command to start, stop, restart, enable or disable a systemd unit in `VITE_SERVICES`. Restricted to admin users.
+/// Runs `systemctl` through `sudo -n`, so the Pi's user needs passwordless sudo for it.
+///
+/// * `Input`: User's name, unit name, and action ("start", "stop", "restart", "enable" or "disable")
+/// * `Output`: State of the unit after the action
+#[command]
+pub async fn manage_service(user_name: String, unit: String, action: String) -> Result<ServiceUnit, String> {
+    require_admin(&user_name)?;
+    if !SERVICE_ACTIONS.contains(&action.as_str()) {
+        return Err(format!("Unknown action '{}': expected one of {}", action, SERVICE_ACTIONS.join(", ")));
+    }
+    let unit = find_allowed_service(&unit)?;
+    let mut session = get_ssh_session().await?;
+
+    let command = format!("sudo -n systemctl {} -- {} 2>&1", action, shell_quote(&unit));
+    let (output, exit_status) = run_remote_command(&mut session, &command)?;
+    if exit_status != 0 {
+        return Err(format!("Failed to {} '{}': {}", action, unit, output.trim()));
+    }
+
+    get_service_units(&mut session, std::slice::from_ref(&unit))?
+        .pop()
+        .ok_or_else(|| format!("Failed to get the state of '{}'", unit))
+}
+
+/// Command to get the most recent `journalctl` lines of a systemd unit in `VITE_SERVICES`. Restricted to admin users.
+///
+/// * `Input`: User's name, unit name, and number of lines (default 100)
+/// * `Output`: Log lines, oldest first
+#[command]
+pub async fn get_service_logs(user_name: String, unit: String, lines: Option<u32>) -> Result<Vec<String>, String> {
+    require_admin(&user_name)?;
+    let unit = find_allowed_service(&unit)?;
+    let lines = lines.unwrap_or(DEFAULT_LOG_LINES).clamp(1, MAX_LOG_LINES);
+    let mut session = get_ssh_session().await?;
+
+    let command = format!("journalctl --no-pager -o short-iso -n {} -u {} 2>&1", lines, shell_quote(&unit));
+    let (output, exit_status) = run_remote_command(&mut session, &command)?;
+    if exit_status != 0 {
+        return Err(format!("Failed to read logs of '{}': {}", unit, output.trim()));
+    }
+
+    Ok(output.lines().map(|line| line.to_string()).collect())
+}
+
+//================================================================================================
+//                              Helper functions for services
+//================================================================================================
+
+/// Loads the systemd units admins may manage from the `VITE_SERVICES` JSON array in the .env file.
+/// Names without a unit type get ".service" appended, as `systemctl` does.
+///
+/// * `Output`: List of unit names, empty if none are configured
+fn load_allowed_services() -> Result<Vec<String>, String> {
+    dotenv::dotenv().ok();
+    let units: Vec<String> = match env::var("VITE_SERVICES") {
+        Ok(units) => serde_json::from_str(&units).map_err(|e| format!("Failed to parse VITE_SERVICES: {}", e))?,
+        Err(_) => vec![],
+    };
+    Ok(units.iter().map(|unit| normalize_unit_name(unit)).collect())
+}
+
+/// Finds a unit in the allow-list.
+///
+/// * `Input`: Unit name, with or without its type
+/// * `Output`: Full unit name
+fn find_allowed_service(unit: &str) -> Result<String, String> {
+    let unit = normalize_unit_name(unit);
+    if load_allowed_services()?.contains(&unit) {
+        Ok(unit)
+    } else {
+        Err(format!("Unit '{}' is not in the list of managed services", unit))
+    }
+}
+
+/// Appends ".service" to a unit name without a type.
+///
+/// * `Input`: Unit name
+/// * `Output`: Full unit name
+fn normalize_unit_name(unit: &str) -> String {
+    let unit = unit.trim();
+    if unit.contains('.') {
+        unit.to_string()
+    } else {
+        format!("{}.service", unit)
+    }
+}
+
+/// Gets the state of systemd units with a single `systemctl show`, which prints one block of properties per unit.
+///
+/// * `Input`: SSH session and unit names
+/// * `Output`: State of each unit, in the given order
+fn get_service_units(session: &mut Session, units: &[String]) -> Result<Vec<ServiceUnit>, String> {
+    let quoted_units: Vec<String> = units.iter().map(|unit| shell_quote(unit)).collect();
+    let command = format!(
+        "systemctl show -p Id,Description,LoadState,ActiveState,SubState,UnitFileState -- {}",
+        quoted_units.join(" "),
+    );
+    let (output, exit_status) = run_remote_command(session, &command)?;
+    if exit_status != 0 {
+        return Err(format!("Failed to get the state of services: systemctl exited with status {}", exit_status));
+    }
+
+    let blocks: Vec<&str> = output.split("\n\n").filter(|block| !block.trim().is_empty()).collect();
+    if blocks.len() != units.len() {
+        return Err("Unexpected output from systemctl show".to_string());
+    }
+
+    Ok(units.iter().zip(blocks).map(|(unit, block)| {
+        let property = |key: &str| -> String {
+            block.lines()
+                .find_map(|line| line.strip_prefix(key).and_then(|line| line.strip_prefix('=')))
+                .unwrap_or("")
+                .to_string()
+        };
+        ServiceUnit {
+            name: unit.clone(),
+            description: property("Description"),
+            load_state: property("LoadState"),
+            active_state: property("ActiveState"),
+            sub_state: property("SubState"),
+            unit_file_state: property("UnitFileState"),
+        }
+    }).collect())
+}
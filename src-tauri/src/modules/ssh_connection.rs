@@ -1,19 +1,16 @@
 use std::{env, fs::{self, File}, io::{Read, Write}, net::TcpStream, path::{Path, PathBuf}};
 
-use serde::Serialize;
-use ssh2::Session;
-use tauri::{api::path::download_dir, command, AppHandle, Manager};
+use ssh2::{FileStat, Session};
+use tauri::{api::path::download_dir, command, AppHandle, Manager, State};
 use zip::{write::FileOptions, ZipWriter};
 use chrono::Utc;
 
-/// Struct to represent a file on the Raspberry Pi.
-#[derive(Debug, Serialize)]
-pub struct FileInfo {
-    pub name: String,
-    pub file_type: String,
-    pub size: u64,
-    pub last_modified: String,
-}
+use super::{
+    file_info::{file_info_from_stat, FileInfo, OwnerNames},
+    mime::{detect_mime_types, MimeCache},
+    permissions::parse_mode,
+    storage::{get_directory_size, StorageCache},
+};
 
 const CHUNK_SIZE: usize = 1 * 1024 * 1024; // 1MB
 
@@ -23,22 +20,26 @@ const CHUNK_SIZE: usize = 1 * 1024 * 1024; // 1MB
 
 /// Uses a .env file to load the IP address, username, and password of the Raspberry Pi.
 /// 
-/// * `Input`: User's name and optional path
+/// * `Input`: User's name, optional path, and the MIME type cache
 /// * `Output`: List of files in the specified directory on the Raspberry Pi
 #[command]
-pub async fn connect_to_pi(user_name: String, path: Option<String>) -> Result<Vec<FileInfo>, String> {
+pub async fn connect_to_pi(user_name: String, path: Option<String>, mime_cache: State<'_, MimeCache>) -> Result<Vec<FileInfo>, String> {
     let mut session = get_ssh_session().await?;
     let home_dir = get_home_directory(&mut session)?;
     let base_dir = verify_base_directory(&mut session, &home_dir)?;
     let remote_dir = create_user_directory(&mut session, &base_dir, &user_name)?;
 
     let target_dir = if let Some(path) = path {
-        format!("{}/{}", remote_dir, path)
+        let target_dir = format!("{}/{}", remote_dir, path);
+        let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+        ensure_in_sandbox(&sftp, &remote_dir, &target_dir)?;
+        target_dir
     } else {
         remote_dir
     };
 
-    let files = list_files_in_directory(&mut session, &target_dir)?;
+    let mut files = list_files_in_directory(&mut session, &target_dir)?;
+    detect_mime_types(&mut session, &target_dir, &mut files, &mime_cache)?;
 
     Ok(files)
 }
@@ -94,26 +95,39 @@ pub async fn download_files(user_name: String, current_path: Vec<String>, file_n
 }
 
 /// Command called by the frontend to upload files to the Raspberry Pi.
-/// * `Input`: User's name, current path, local file paths, and app handle for emitting events
+/// If a mode is given (octal or symbolic, as accepted by `set_permissions`), it is applied to each uploaded file.
+///
+/// * `Input`: User's name, current path, local file paths, optional mode, app handle for emitting events, and the storage cache
 /// * `Output`: None
 #[command]
-pub async fn upload_files(user_name: String, current_path: Vec<String>, local_file_paths: Vec<String>, app_handle: AppHandle) -> Result<(), String> {
+pub async fn upload_files(user_name: String, current_path: Vec<String>, local_file_paths: Vec<String>, mode: Option<String>, app_handle: AppHandle, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
     let (mut session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
 
     for local_file_path in local_file_paths {
         let file_name = Path::new(&local_file_path).file_name().unwrap().to_str().unwrap();
         let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
         upload_file_in_chunks(&mut session, &remote_file_path, Path::new(&local_file_path), &app_handle)?;
+
+        if let Some(mode) = &mode {
+            let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+            let stat = sftp.stat(Path::new(&remote_file_path))
+                .map_err(|e| format!("Failed to stat remote file '{}': {}", remote_file_path, e))?;
+            let new_mode = parse_mode(mode, stat.perm.unwrap_or(0) & 0o7777, false)?;
+            sftp.setstat(Path::new(&remote_file_path), FileStat { size: None, uid: None, gid: None, perm: Some(new_mode), atime: None, mtime: None })
+                .map_err(|e| format!("Failed to set mode of '{}': {}", remote_file_path, e))?;
+        }
     }
 
     Ok(())
 }
 
 /// Command to create a new folder in the current directory.
-/// * `Input`: User's name, current path, folder name
+/// * `Input`: User's name, current path, folder name, and the storage cache
 /// * `Output`: None
 #[command]
-pub async fn create_folder(user_name: String, current_path: Vec<String>, folder_name: String) -> Result<(), String> {
+pub async fn create_folder(user_name: String, current_path: Vec<String>, folder_name: String, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
     let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
 
     let remote_folder_path = format!("{}/{}", current_remote_dir, folder_name);
@@ -125,10 +139,11 @@ pub async fn create_folder(user_name: String, current_path: Vec<String>, folder_
 }
 
 /// Command to rename a file or folder in the current directory.
-/// * `Input`: User's name, current path, old name, new name
+/// * `Input`: User's name, current path, old name, new name, and the storage cache
 /// * `Output`: None
 #[command]
-pub async fn rename_file(user_name: String, current_path: Vec<String>, old_name: String, new_name: String) -> Result<(), String> {
+pub async fn rename_file(user_name: String, current_path: Vec<String>, old_name: String, new_name: String, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
     let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
 
     let old_file_path = format!("{}/{}", current_remote_dir, old_name);
@@ -141,65 +156,6 @@ pub async fn rename_file(user_name: String, current_path: Vec<String>, old_name:
     Ok(())
 }
 
-/// Command to delete files or folders in the current directory.
-/// * `Input`: User's name, current path, file names
-/// * `Output`: None
-#[command]
-pub async fn delete_files(user_name: String, current_path: Vec<String>, file_names: Vec<String>) -> Result<(), String> {
-    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
-
-    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
-
-    for file_name in file_names {
-        let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
-        let path = Path::new(&remote_file_path);
-        if sftp.stat(path).map_err(|e| format!("Failed to stat '{}': {}", remote_file_path, e))?.is_dir() {
-            recursive_delete(&sftp, path)?;
-        } else {
-            sftp.unlink(path).map_err(|e| format!("Failed to delete file '{}': {}", remote_file_path, e))?;
-        }
-    }
-
-    Ok(())
-}
-
-/// Command to read the content of a file.
-/// * `Input`: User's name, current path, file name
-/// * `Output`: File content as a string
-#[command]
-pub async fn read_file(user_name: String, current_path: Vec<String>, file_name: String) -> Result<String, String> {
-    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
-
-    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
-    let path = Path::new(&remote_file_path);
-
-    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
-    let mut remote_file = sftp.open(path).map_err(|e| format!("Failed to open file '{}': {}", remote_file_path, e))?;
-
-    let mut contents = String::new();
-    remote_file.read_to_string(&mut contents).map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
-
-    Ok(contents)
-}
-
-/// Command to save the content to a file.
-/// * `Input`: User's name, current path, file name, file content
-/// * `Output`: None
-#[command]
-pub async fn save_file(user_name: String, current_path: Vec<String>, file_name: String, file_content: String) -> Result<(), String> {
-    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
-
-    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
-    let path = Path::new(&remote_file_path);
-
-    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
-    let mut remote_file = sftp.create(path).map_err(|e| format!("Failed to create file '{}': {}", remote_file_path, e))?;
-
-    remote_file.write_all(file_content.as_bytes()).map_err(|e| format!("Failed to write to file '{}': {}", remote_file_path, e))?;
-
-    Ok(())
-}
-
 /// Command to get the storage used by the user in the base remote directory.
 /// * `Input`: User's name
 /// * `Output`: Storage used in bytes
@@ -209,21 +165,7 @@ pub async fn get_storage_used(user_name: String) -> Result<u64, String> {
     let home_dir = get_home_directory(&mut session)?;
     let remote_dir = format!("{}/{}/{}", home_dir, "pi-interface", user_name);
 
-    let mut channel = session.channel_session().map_err(|e| format!("Failed to open channel: {}", e))?;
-    channel.exec(&format!("du -sb \"{}\"", remote_dir)).map_err(|e| format!("Failed to execute command: {}", e))?;
-
-    let mut s = String::new();
-    channel.read_to_string(&mut s).map_err(|e| format!("Failed to read from channel: {}", e))?;
-    channel.close().map_err(|e| format!("Failed to close channel: {}", e))?;
-    channel.wait_close().map_err(|e| format!("Failed to wait for channel close: {}", e))?;
-
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    if parts.len() < 1 {
-        return Err("Unexpected output from du command".to_string());
-    }
-
-    let size: u64 = parts[0].parse().map_err(|e| format!("Failed to parse size: {}", e))?;
-    Ok(size)
+    get_directory_size(&mut session, &remote_dir)
 }
 
 //================================================================================================
@@ -231,6 +173,7 @@ pub async fn get_storage_used(user_name: String) -> Result<u64, String> {
 //================================================================================================
 
 /// Gets the remote directories and session for the user.
+/// Fails if the current path leads outside the user's directory, e.g. through `..` or a symlink.
 ///     
 /// * `Input`: User's name and current path
 /// * `Output`: SSH session, remote directory, and current remote directory
@@ -241,12 +184,59 @@ pub async fn get_remote_dirs_and_session(user_name: String, current_path: Vec<St
     let current_remote_dir = if current_path.is_empty() {
         remote_dir.clone()
     } else {
-        format!("{}/{}", remote_dir, current_path.join("/"))
+        let current_remote_dir = format!("{}/{}", remote_dir, current_path.join("/"));
+        let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+        ensure_in_sandbox(&sftp, &remote_dir, &current_remote_dir)?;
+        current_remote_dir
     };
 
     Ok((session, remote_dir, current_remote_dir))
 }
 
+/// Ensures a remote path, with all symlinks resolved, is inside the user's directory.
+///
+/// * `Input`: SFTP session, user's directory, and remote path
+/// * `Output`: Resolved remote path
+pub fn ensure_in_sandbox(sftp: &ssh2::Sftp, remote_dir: &str, remote_path: &str) -> Result<String, String> {
+    let real_remote_dir = sftp.realpath(Path::new(remote_dir))
+        .map_err(|e| format!("Failed to resolve '{}': {}", remote_dir, e))?;
+    let real_path = sftp.realpath(Path::new(remote_path))
+        .map_err(|e| format!("Failed to resolve '{}': {}", remote_path, e))?;
+    if !real_path.starts_with(&real_remote_dir) {
+        return Err(format!("'{}' is outside of the user's directory", remote_path));
+    }
+    Ok(real_path.to_string_lossy().to_string())
+}
+
+/// Ensures a file name from the frontend names an entry of the current directory, not a path leading elsewhere.
+///
+/// * `Input`: File name
+/// * `Output`: None
+pub fn validate_file_name(file_name: &str) -> Result<(), String> {
+    if file_name.is_empty() || file_name == "." || file_name == ".." || file_name.contains('/') {
+        return Err(format!("Invalid file name '{}'", file_name));
+    }
+    Ok(())
+}
+
+/// Normalizes a relative path lexically, resolving `.` and `..` components.
+///
+/// * `Input`: Relative path
+/// * `Output`: Normalized path, or None if it climbs above its starting directory
+pub fn normalize_relative_path(path: &str) -> Option<String> {
+    let mut components: Vec<&str> = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
 
 /// Gets an SSH session with the Raspberry Pi.
 ///     
@@ -294,7 +284,7 @@ fn establish_ssh_session(pi_ip: String, pi_username: String, pi_password: String
 /// 
 /// * `Input`: SSH session
 /// * `Output`: Home directory
-fn get_home_directory(session: &mut Session) -> Result<String, String> {
+pub fn get_home_directory(session: &mut Session) -> Result<String, String> {
     let mut channel = session.channel_session().map_err(|e| format!("Failed to create channel session: {}", e))?;
     channel.exec("echo $HOME").map_err(|e| format!("Failed to execute command to get home directory: {}", e))?;
     let mut home_dir = String::new();
@@ -307,6 +297,31 @@ fn get_home_directory(session: &mut Session) -> Result<String, String> {
     Ok(home_dir.trim().to_string()) // Remove any trailing newline or whitespace
 }
 
+/// Runs a shell command on the Raspberry Pi and captures its output.
+/// Output that is not valid UTF-8 is converted lossily.
+///
+/// * `Input`: SSH session and command
+/// * `Output`: Standard output and exit status of the command
+pub fn run_remote_command(session: &mut Session, command: &str) -> Result<(String, i32), String> {
+    let mut channel = session.channel_session().map_err(|e| format!("Failed to open channel: {}", e))?;
+    channel.exec(command).map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    let mut output = vec![];
+    channel.read_to_end(&mut output).map_err(|e| format!("Failed to read from channel: {}", e))?;
+    channel.wait_close().map_err(|e| format!("Failed to wait for channel close: {}", e))?;
+    let exit_status = channel.exit_status().map_err(|e| format!("Failed to get exit status: {}", e))?;
+
+    Ok((String::from_utf8_lossy(&output).into_owned(), exit_status))
+}
+
+/// Quotes a value so it is passed to a remote shell command as a single literal argument.
+///
+/// * `Input`: Value to quote
+/// * `Output`: Single-quoted value
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 
 /// Verifies the existence of the base directory on the Raspberry Pi.
 /// If the base directory does not exist, it is created.
@@ -339,24 +354,14 @@ fn create_user_directory(session: &mut Session, base_dir: &str, user_name: &str)
 /// 
 /// * `Input`: SSH session and directory
 /// * `Output`: List of files in the directory
-fn list_files_in_directory(session: &mut Session, remote_dir: &str) -> Result<Vec<FileInfo>, String> {
+pub fn list_files_in_directory(session: &mut Session, remote_dir: &str) -> Result<Vec<FileInfo>, String> {
     let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let owners = OwnerNames::load(&sftp);
     let mut files = vec![];
     let entries = sftp.readdir(Path::new(remote_dir)).map_err(|e| format!("Failed to read directory {}: {}", remote_dir, e))?;
     for (path, stat) in entries {
-        if let Some(file_name) = path.file_name() {
-            let file_type = if stat.is_file() {
-                path.extension()
-                    .map_or_else(|| "Unknown".to_string(), |ext| ext.to_string_lossy().into_owned())
-            } else {
-                "Folder".to_string()
-            };
-            files.push(FileInfo {
-                name: file_name.to_string_lossy().to_string(),
-                file_type,
-                size: stat.size.unwrap_or(0),
-                last_modified: stat.mtime.unwrap_or(0).to_string(),
-            });
+        if let Some(file_info) = file_info_from_stat(&sftp, &path, &stat, &owners) {
+            files.push(file_info);
         }
     }
     Ok(files)
@@ -546,14 +551,33 @@ fn upload_file_in_chunks(session: &mut Session, remote_file_path: &str, local_fi
     Ok(())
 }
 
+/// Creates a remote directory along with any missing parent directories.
+///
+/// * `Input`: SFTP session and absolute directory path
+/// * `Output`: None
+pub fn create_remote_dir_all(sftp: &ssh2::Sftp, path: &str) -> Result<(), String> {
+    let mut dir = String::new();
+    for folder in path.split('/').filter(|folder| !folder.is_empty()) {
+        dir = format!("{}/{}", dir, folder);
+        if sftp.stat(Path::new(&dir)).is_err() {
+            sftp.mkdir(Path::new(&dir), 0o755).map_err(|e| format!("Failed to create directory {}: {}", dir, e))?;
+        }
+    }
+    Ok(())
+}
+
 /// Recursively delete a directory and its contents.
+/// Symbolic links are deleted themselves, without descending into what they point to.
 /// 
 /// * `Input`: SFTP session and directory path
 /// * `Output`: None
-fn recursive_delete(sftp: &ssh2::Sftp, path: &Path) -> Result<(), String> {
+pub fn recursive_delete(sftp: &ssh2::Sftp, path: &Path) -> Result<(), String> {
+    if !sftp.lstat(path).map_err(|e| format!("Failed to stat '{}': {}", path.display(), e))?.is_dir() {
+        return sftp.unlink(path).map_err(|e| format!("Failed to delete file '{}': {}", path.display(), e));
+    }
     let entries = sftp.readdir(path).map_err(|e| format!("Failed to read directory '{}': {}", path.display(), e))?;
     for (entry_path, _) in entries {
-        if sftp.stat(&entry_path).map_err(|e| format!("Failed to stat '{}': {}", entry_path.display(), e))?.is_dir() {
+        if sftp.lstat(&entry_path).map_err(|e| format!("Failed to stat '{}': {}", entry_path.display(), e))?.is_dir() {
             recursive_delete(sftp, &entry_path)?;
         } else {
             sftp.unlink(&entry_path).map_err(|e| format!("Failed to delete file '{}': {}", entry_path.display(), e))?;
@@ -0,0 +1,347 @@
+use std::{cmp::Reverse, collections::HashMap, env, path::Path, sync::Mutex};
+
+use serde::Serialize;
+use ssh2::Session;
+use chrono::Utc;
+use tauri::{command, AppHandle, Manager, State};
+
+use super::{
+    ssh_connection::{get_home_directory, get_remote_dirs_and_session, run_remote_command, shell_quote},
+    users::find_user,
+};
+
+/// Struct to represent the usage of a filesystem mounted on the Raspberry Pi.
+/// Sizes are in bytes.
+#[derive(Debug, Clone, Serialize)]
+pub struct FilesystemUsage {
+    pub filesystem: String,
+    pub mount_point: String,
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    /// Percentage of the space usable by regular users that is used, as reported by `df`
+    pub percent_used: f64,
+}
+
+/// Struct to represent the storage report of a user.
+/// Sizes are in bytes.
+#[derive(Debug, Serialize)]
+pub struct StorageReport {
+    pub filesystems: Vec<FilesystemUsage>,
+    pub user_used: u64,
+    /// Storage limit of the user, or None if the user has no limit
+    pub user_limit: Option<u64>,
+    pub user_percent_used: Option<f64>,
+    pub warning_percent: f64,
+}
+
+/// Struct to represent a warning emitted when a filesystem or a user's storage is almost full.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageWarning {
+    /// Either "filesystem" or "user"
+    pub scope: String,
+    /// Mount point of the filesystem, or the user's name
+    pub name: String,
+    pub percent_used: f64,
+    pub warning_percent: f64,
+}
+
+/// Struct to represent the size of a folder and its subfolders, sorted from largest to smallest.
+#[derive(Debug, Clone, Serialize)]
+pub struct FolderSize {
+    pub name: String,
+    /// Path relative to the user's directory, empty for the user's directory itself
+    pub path: String,
+    pub size: u64,
+    pub children: Vec<FolderSize>,
+}
+
+/// Struct to represent one of the largest files in the user's directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct LargeFile {
+    /// Path relative to the user's directory
+    pub path: String,
+    pub size: u64,
+}
+
+/// Struct to represent what takes up the space in the user's directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageBreakdown {
+    pub root: FolderSize,
+    pub largest_files: Vec<LargeFile>,
+    /// When the breakdown was computed, in ISO format
+    pub computed_at: String,
+}
+
+/// Cache of storage breakdowns, keyed by user and the depth and file count they were computed with.
+/// Commands that change the user's directory invalidate it, since `du` is slow on large trees.
+#[derive(Default)]
+pub struct StorageCache(Mutex<HashMap<String, (usize, usize, StorageBreakdown)>>);
+
+impl StorageCache {
+    /// Drops the cached breakdown of a user.
+    ///
+    /// * `Input`: User's name
+    pub fn invalidate(&self, user_name: &str) {
+        if let Ok(mut cached) = self.0.lock() {
+            cached.remove(&user_name.to_lowercase());
+        }
+    }
+}
+
+const DEFAULT_STORAGE_WARNING_PERCENT: f64 = 90.0;
+const DEFAULT_BREAKDOWN_DEPTH: usize = 3;
+const DEFAULT_LARGEST_FILES: usize = 20;
+
+//================================================================================================
+//                              Commands for storage
+//================================================================================================
+
+/// Command to get the free space of the Raspberry Pi's filesystems and the user's usage against their limit.
+/// Emits a "storage-warning" event for each filesystem or user limit above the warning percentage.
+///
+/// * `Input`: User's name and warning percentage, defaulting to `VITE_STORAGE_WARNING_PERCENT` or 90
+/// * `Output`: Storage report
+#[command]
+pub async fn get_storage_report(user_name: String, warning_percent: Option<f64>, app_handle: AppHandle) -> Result<StorageReport, String> {
+    let user = find_user(&user_name)?;
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name, vec![]).await?;
+    let warning_percent = warning_percent.unwrap_or_else(get_storage_warning_percent);
+
+    let filesystems = get_filesystem_usage(&mut session)?;
+    let user_used = get_directory_size(&mut session, &remote_dir)?;
+    // Storage limits are configured in GB
+    let user_limit = Some((user.storage_limit * 1e9) as u64).filter(|limit| *limit > 0);
+    let user_percent_used = user_limit.map(|limit| user_used as f64 / limit as f64 * 100.0);
+
+    for filesystem in filesystems.iter().filter(|filesystem| filesystem.percent_used >= warning_percent) {
+        let warning = StorageWarning {
+            scope: "filesystem".to_string(),
+            name: filesystem.mount_point.clone(),
+            percent_used: filesystem.percent_used,
+            warning_percent,
+        };
+        app_handle.emit_all("storage-warning", warning).unwrap();
+    }
+    if let Some(percent_used) = user_percent_used.filter(|percent_used| *percent_used >= warning_percent) {
+        let warning = StorageWarning { scope: "user".to_string(), name: user.name.clone(), percent_used, warning_percent };
+        app_handle.emit_all("storage-warning", warning).unwrap();
+    }
+
+    Ok(StorageReport { filesystems, user_used, user_limit, user_percent_used, warning_percent })
+}
+
+/// Command to get the folder sizes and largest files in the user's directory.
+/// Computed with `du` and `find` on the Raspberry Pi and cached until the user's directory changes.
+///
+/// * `Input`: User's name, folder depth (default 3), number of largest files (default 20), whether to bypass the cache, and the cache
+/// * `Output`: Storage breakdown
+#[command]
+pub async fn get_storage_breakdown(user_name: String, max_depth: Option<usize>, top_files: Option<usize>, refresh: Option<bool>, storage_cache: State<'_, StorageCache>) -> Result<StorageBreakdown, String> {
+    let max_depth = max_depth.unwrap_or(DEFAULT_BREAKDOWN_DEPTH);
+    let top_files = top_files.unwrap_or(DEFAULT_LARGEST_FILES);
+    let cache_key = user_name.to_lowercase();
+    if !refresh.unwrap_or(false) {
+        let cached = storage_cache.0.lock().map_err(|_| "Storage cache is poisoned".to_string())?;
+        if let Some((depth, files, breakdown)) = cached.get(&cache_key) {
+            if *depth == max_depth && *files == top_files {
+                return Ok(breakdown.clone());
+            }
+        }
+    }
+
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name, vec![]).await?;
+    let quoted_dir = shell_quote(&remote_dir);
+
+    // Null-separated output keeps names containing newlines intact
+    let du_command = format!("du -b -0 --max-depth={} {}", max_depth, quoted_dir);
+    let (output, exit_status) = run_remote_command(&mut session, &du_command)?;
+    if exit_status != 0 && output.is_empty() {
+        return Err(format!("du command failed with exit status: {}", exit_status));
+    }
+    let mut folders = vec![];
+    for entry in output.split('\0').filter(|entry| !entry.is_empty()) {
+        let (size, path) = entry.split_once('\t').ok_or("Unexpected output from du command")?;
+        let size = size.parse().map_err(|e| format!("Failed to parse size: {}", e))?;
+        let relative_path = path.strip_prefix(remote_dir.as_str()).unwrap_or(path).trim_start_matches('/');
+        folders.push((relative_path.to_string(), size));
+    }
+
+    let find_command = format!("find {} -type f -printf '%s\\t%P\\0' | sort -z -n -r | head -z -n {}", quoted_dir, top_files);
+    let (output, _) = run_remote_command(&mut session, &find_command)?;
+    let largest_files = output.split('\0')
+        .filter_map(|entry| {
+            let (size, path) = entry.split_once('\t')?;
+            Some(LargeFile { path: path.to_string(), size: size.parse().ok()? })
+        })
+        .collect();
+
+    let breakdown = StorageBreakdown {
+        root: build_folder_tree(folders),
+        largest_files,
+        computed_at: Utc::now().to_rfc3339(),
+    };
+    let mut cached = storage_cache.0.lock().map_err(|_| "Storage cache is poisoned".to_string())?;
+    cached.insert(cache_key, (max_depth, top_files, breakdown.clone()));
+
+    Ok(breakdown)
+}
+
+//================================================================================================
+//                              Helper functions for storage
+//================================================================================================
+
+/// Gets the size of a remote directory with `du`.
+///
+/// * `Input`: SSH session and remote directory
+/// * `Output`: Size of the directory in bytes
+pub fn get_directory_size(session: &mut Session, remote_dir: &str) -> Result<u64, String> {
+    let (output, _) = run_remote_command(session, &format!("du -sb {}", shell_quote(remote_dir)))?;
+    let size = output.split_whitespace().next().ok_or("Unexpected output from du command")?;
+    size.parse().map_err(|e| format!("Failed to parse size: {}", e))
+}
+
+/// Builds a tree of folder sizes from `du` entries.
+///
+/// * `Input`: Paths relative to the user's directory and their sizes
+/// * `Output`: Root folder of the tree
+fn build_folder_tree(mut folders: Vec<(String, u64)>) -> FolderSize {
+    // Parents are inserted before their children
+    folders.sort_by_key(|(path, _)| if path.is_empty() { 0 } else { path.matches('/').count() + 1 });
+
+    let mut root = FolderSize { name: String::new(), path: String::new(), size: 0, children: vec![] };
+    for (path, size) in folders {
+        let mut folder = &mut root;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let index = match folder.children.iter().position(|child| child.name == name) {
+                Some(index) => index,
+                None => {
+                    let child_path = if folder.path.is_empty() { name.to_string() } else { format!("{}/{}", folder.path, name) };
+                    folder.children.push(FolderSize { name: name.to_string(), path: child_path, size: 0, children: vec![] });
+                    folder.children.len() - 1
+                }
+            };
+            folder = &mut folder.children[index];
+        }
+        folder.size = size;
+    }
+
+    sort_folder_tree(&mut root);
+    root
+}
+
+/// Sorts the subfolders of a folder tree from largest to smallest.
+///
+/// * `Input`: Root folder of the tree
+fn sort_folder_tree(folder: &mut FolderSize) {
+    folder.children.sort_by_key(|child| Reverse(child.size));
+    for child in &mut folder.children {
+        sort_folder_tree(child);
+    }
+}
+
+/// Gets the usage of the Raspberry Pi's filesystems with `df`.
+/// Falls back to `statvfs` over SFTP for the filesystem holding the home directory if `df` fails.
+///
+/// * `Input`: SSH session
+/// * `Output`: Usage of each filesystem
+fn get_filesystem_usage(session: &mut Session) -> Result<Vec<FilesystemUsage>, String> {
+    let (output, exit_status) = run_remote_command(session, "df -P -B1 -x tmpfs -x devtmpfs")?;
+    let filesystems: Vec<FilesystemUsage> = output.lines().skip(1).filter_map(parse_df_line).collect();
+    if exit_status == 0 && !filesystems.is_empty() {
+        return Ok(filesystems);
+    }
+
+    let home_dir = get_home_directory(session)?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let mut dir = sftp.opendir(Path::new(&home_dir)).map_err(|e| format!("Failed to open directory '{}': {}", home_dir, e))?;
+    let stat = dir.statvfs().map_err(|e| format!("Failed to get filesystem usage of '{}': {}", home_dir, e))?;
+
+    let total = stat.f_blocks * stat.f_frsize;
+    let used = (stat.f_blocks - stat.f_bfree) * stat.f_frsize;
+    let available = stat.f_bavail * stat.f_frsize;
+    Ok(vec![FilesystemUsage {
+        filesystem: String::new(),
+        mount_point: home_dir,
+        total,
+        used,
+        available,
+        percent_used: percent_used(used, available),
+    }])
+}
+
+/// Parses a line of `df -P -B1` output.
+/// The mount point is the last column and may contain spaces.
+///
+/// * `Input`: Line of output
+/// * `Output`: Usage of the filesystem, or None if the line is malformed
+fn parse_df_line(line: &str) -> Option<FilesystemUsage> {
+    let mut columns = line.split_whitespace();
+    let filesystem = columns.next()?.to_string();
+    let total = columns.next()?.parse().ok()?;
+    let used = columns.next()?.parse().ok()?;
+    let available = columns.next()?.parse().ok()?;
+    columns.next()?; // Capacity, recomputed below for more precision
+    let mount_point = columns.collect::<Vec<_>>().join(" ");
+    if mount_point.is_empty() {
+        return None;
+    }
+
+    Some(FilesystemUsage { filesystem, mount_point, total, used, available, percent_used: percent_used(used, available) })
+}
+
+/// Computes the used percentage of a filesystem the way `df` does, ignoring space reserved for root.
+///
+/// * `Input`: Used and available bytes
+/// * `Output`: Used percentage
+fn percent_used(used: u64, available: u64) -> f64 {
+    if used + available == 0 {
+        return 0.0;
+    }
+    used as f64 / (used + available) as f64 * 100.0
+}
+
+/// Gets the percentage above which storage warnings are emitted.
+/// Read from `VITE_STORAGE_WARNING_PERCENT`, defaulting to 90.
+///
+/// * `Output`: Warning percentage
+fn get_storage_warning_percent() -> f64 {
+    dotenv::dotenv().ok();
+    env::var("VITE_STORAGE_WARNING_PERCENT").ok()
+        .and_then(|percent| percent.trim().parse().ok())
+        .unwrap_or(DEFAULT_STORAGE_WARNING_PERCENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_df_line_reads_the_columns() {
+        let usage = parse_df_line("/dev/root      31250000000 7812500000 22187500000      27% /").unwrap();
+        assert_eq!(usage.filesystem, "/dev/root");
+        assert_eq!(usage.mount_point, "/");
+        assert_eq!((usage.total, usage.used, usage.available), (31250000000, 7812500000, 22187500000));
+        assert!((usage.percent_used - 26.041666).abs() < 0.001);
+    }
+
+    #[test]
+    fn parse_df_line_keeps_spaces_in_the_mount_point() {
+        let usage = parse_df_line("/dev/sda1 1000 250 750 25% /media/pi/My Drive").unwrap();
+        assert_eq!(usage.mount_point, "/media/pi/My Drive");
+        assert_eq!(usage.percent_used, 25.0);
+    }
+
+    #[test]
+    fn parse_df_line_rejects_malformed_lines() {
+        assert!(parse_df_line("Filesystem 1-blocks Used Available Capacity Mounted on").is_none());
+        assert!(parse_df_line("/dev/root 1000 250 750 25%").is_none());
+        assert!(parse_df_line("").is_none());
+    }
+
+    #[test]
+    fn percent_used_handles_empty_filesystems() {
+        assert_eq!(percent_used(0, 0), 0.0);
+        assert_eq!(percent_used(1, 3), 25.0);
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use serde::Serialize;
+use ssh2::Sftp;
+use tauri::{command, State};
+
+use super::{
+    file_info::{kind_from_stat, FileKind},
+    ssh_connection::{ensure_in_sandbox, get_remote_dirs_and_session, normalize_relative_path},
+    storage::StorageCache,
+};
+
+/// Struct to represent where a symbolic link points.
+#[derive(Debug, Serialize)]
+pub struct LinkInfo {
+    /// Target as stored in the link
+    pub target: String,
+    /// Target relative to the user's directory, or None if it is outside of it
+    pub relative_target: Option<String>,
+    /// Kind of the target, or None if the link is broken
+    pub target_kind: Option<FileKind>,
+    pub is_broken: bool,
+}
+
+//================================================================================================
+//                              Commands for symbolic links
+//================================================================================================
+
+/// Command to create a symbolic link in the current directory.
+/// The target must be inside the user's directory, even after resolving any symlinks along its path.
+///
+/// * `Input`: User's name, current path, link name, target path relative to the user's directory, and the storage cache
+/// * `Output`: None
+#[command]
+pub async fn create_symlink(user_name: String, current_path: Vec<String>, link_name: String, target: String, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
+    let (session, remote_dir, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_target = normalize_relative_path(&target)
+        .filter(|relative_target| !relative_target.is_empty())
+        .ok_or_else(|| format!("Link target '{}' is outside of the user's directory", target))?;
+    if link_name.is_empty() || link_name == "." || link_name == ".." || link_name.contains('/') {
+        return Err(format!("Invalid link name '{}'", link_name));
+    }
+    let target_path = format!("{}/{}", remote_dir, relative_target);
+    ensure_in_sandbox(&sftp, &remote_dir, &nearest_existing_path(&sftp, &remote_dir, &relative_target))?;
+
+    let link_path = format!("{}/{}", current_remote_dir, link_name);
+    if sftp.lstat(Path::new(&link_path)).is_ok() {
+        return Err(format!("Cannot create link '{}': an item with that name already exists", link_name));
+    }
+    // Creates a link at the second path pointing at the first
+    sftp.symlink(Path::new(&target_path), Path::new(&link_path))
+        .map_err(|e| format!("Failed to create link '{}': {}", link_path, e))?;
+
+    Ok(())
+}
+
+/// Command to read where a symbolic link in the current directory points.
+///
+/// * `Input`: User's name, current path, and link name
+/// * `Output`: Link target and whether it resolves inside the user's directory
+#[command]
+pub async fn read_link(user_name: String, current_path: Vec<String>, link_name: String) -> Result<LinkInfo, String> {
+    let (session, remote_dir, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let link_path = format!("{}/{}", current_remote_dir, link_name);
+    let target = sftp.readlink(Path::new(&link_path))
+        .map_err(|e| format!("Failed to read link '{}': {}", link_path, e))?
+        .to_string_lossy()
+        .to_string();
+
+    // Broken links cannot be resolved, and links leading outside are reported without a relative target
+    let (relative_target, target_kind) = match sftp.stat(Path::new(&link_path)) {
+        Ok(stat) => {
+            let relative_target = ensure_in_sandbox(&sftp, &remote_dir, &link_path).ok().and_then(|real_path| {
+                let real_remote_dir = sftp.realpath(Path::new(&remote_dir)).ok()?;
+                let relative = Path::new(&real_path).strip_prefix(real_remote_dir).ok()?;
+                Some(relative.to_string_lossy().to_string())
+            });
+            (relative_target, Some(kind_from_stat(&stat)))
+        }
+        Err(_) => (None, None),
+    };
+
+    Ok(LinkInfo { target, relative_target, is_broken: target_kind.is_none(), target_kind })
+}
+
+//================================================================================================
+//                              Helper functions for symbolic links
+//================================================================================================
+
+/// Finds the deepest existing path along a path relative to the user's directory,
+/// so a target that does not exist yet is checked through the directories it would be created in.
+///
+/// * `Input`: SFTP session, user's directory, and normalized relative path
+/// * `Output`: Remote path of the path itself or its nearest existing parent
+fn nearest_existing_path(sftp: &Sftp, remote_dir: &str, relative_path: &str) -> String {
+    let mut relative_path = relative_path;
+    loop {
+        let remote_path = format!("{}/{}", remote_dir, relative_path);
+        if sftp.lstat(Path::new(&remote_path)).is_ok() {
+            return remote_path;
+        }
+        match relative_path.rsplit_once('/') {
+            Some((parent, _)) => relative_path = parent,
+            None => return remote_dir.to_string(),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    sync::{mpsc::{self, RecvTimeoutError, Sender}, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use ssh2::Session;
+use tauri::{async_runtime::block_on, command, AppHandle, Manager, State};
+
+use super::{
+    ssh_connection::{get_ssh_session, run_remote_command},
+    users::find_user,
+};
+
+/// Struct to represent the health of the Raspberry Pi.
+/// Fields are None when the Pi does not report them.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemStatus {
+    /// CPU temperature in degrees Celsius
+    pub cpu_temperature: Option<f64>,
+    /// Load averages over 1, 5 and 15 minutes
+    pub load_average: Option<[f64; 3]>,
+    pub cpu_count: Option<u32>,
+    pub memory: Option<MemoryUsage>,
+    pub swap: Option<MemoryUsage>,
+    /// Uptime in seconds
+    pub uptime: Option<f64>,
+    pub throttling: Option<ThrottleStatus>,
+    pub network_interfaces: Vec<NetworkInterface>,
+}
+
+/// Struct to represent the usage of memory or swap, in bytes.
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+}
+
+/// Struct to represent the throttling flags reported by `vcgencmd get_throttled`.
+/// The `_occurred` flags stay set from the first time the condition happened since boot.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThrottleStatus {
+    pub raw: u32,
+    pub under_voltage: bool,
+    pub frequency_capped: bool,
+    pub throttled: bool,
+    pub soft_temperature_limit: bool,
+    pub under_voltage_occurred: bool,
+    pub frequency_capped_occurred: bool,
+    pub throttled_occurred: bool,
+    pub soft_temperature_limit_occurred: bool,
+}
+
+/// Struct to represent a network interface and its addresses, in CIDR notation.
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub addresses: Vec<String>,
+}
+
+/// Sender stopping the thread that polls the system status, if one is running.
+#[derive(Default)]
+pub struct SystemStatusPoller(Mutex<Option<Sender<()>>>);
+
+/// Separates the outputs of the commands read at once by `STATUS_COMMAND`.
+const SECTION_SEPARATOR: &str = "--pi-interface-section--";
+const STATUS_COMMAND: &str = "cat /sys/class/thermal/thermal_zone0/temp 2>/dev/null || vcgencmd measure_temp 2>/dev/null; \
+    echo --pi-interface-section--; cat /proc/loadavg; \
+    echo --pi-interface-section--; nproc 2>/dev/null; \
+    echo --pi-interface-section--; cat /proc/meminfo; \
+    echo --pi-interface-section--; cat /proc/uptime; \
+    echo --pi-interface-section--; vcgencmd get_throttled 2>/dev/null; \
+    echo --pi-interface-section--; ip -o addr show 2>/dev/null";
+const MIN_POLL_INTERVAL: u64 = 1;
+
+//================================================================================================
+//                              Commands for system status
+//================================================================================================
+
+/// Command to get the CPU temperature, load, memory, uptime, throttling and network addresses of the Raspberry Pi.
+///
+/// * `Input`: User's name
+/// * `Output`: System status
+#[command]
+pub async fn get_system_status(user_name: String) -> Result<SystemStatus, String> {
+    find_user(&user_name)?;
+    let mut session = get_ssh_session().await?;
+    read_system_status(&mut session)
+}
+
+/// Command to emit the system status as a "system-status" event at a fixed interval, until polling is stopped.
+/// A poll that fails emits the error as a "system-status-error" event instead. Replaces any polling already running.
+///
+/// * `Input`: User's name, interval in seconds, app handle for emitting events, and the poller
+/// * `Output`: None
+#[command]
+pub async fn start_system_status_polling(user_name: String, interval: u64, app_handle: AppHandle, poller: State<'_, SystemStatusPoller>) -> Result<(), String> {
+    find_user(&user_name)?;
+    let mut session = get_ssh_session().await?;
+    let interval = Duration::from_secs(interval.max(MIN_POLL_INTERVAL));
+
+    let (sender, receiver) = mpsc::channel();
+    let mut running = poller.0.lock().map_err(|_| "System status poller is poisoned".to_string())?;
+    if let Some(previous) = running.replace(sender) {
+        previous.send(()).ok();
+    }
+
+    thread::spawn(move || loop {
+        match read_system_status(&mut session) {
+            Ok(status) => app_handle.emit_all("system-status", status).unwrap(),
+            // Reconnect on the next poll if the connection was lost
+            Err(e) => {
+                app_handle.emit_all("system-status-error", e).unwrap();
+                if let Ok(new_session) = block_on(get_ssh_session()) {
+                    session = new_session;
+                }
+            }
+        }
+        match receiver.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    });
+
+    Ok(())
+}
+
+/// Command to stop polling the system status.
+///
+/// * `Input`: The poller
+/// * `Output`: None
+#[command]
+pub async fn stop_system_status_polling(poller: State<'_, SystemStatusPoller>) -> Result<(), String> {
+    let mut running = poller.0.lock().map_err(|_| "System status poller is poisoned".to_string())?;
+    if let Some(sender) = running.take() {
+        sender.send(()).ok();
+    }
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for system status
+//================================================================================================
+
+/// Reads the system status of the Raspberry Pi with a single command and parses its sections.
+///
+/// * `Input`: SSH session
+/// * `Output`: System status
+fn read_system_status(session: &mut Session) -> Result<SystemStatus, String> {
+    let (output, _) = run_remote_command(session, STATUS_COMMAND)?;
+    let sections: Vec<&str> = output.split(SECTION_SEPARATOR).map(str::trim).collect();
+    if sections.len() != 7 {
+        return Err("Unexpected output from system status command".to_string());
+    }
+
+    Ok(SystemStatus {
+        cpu_temperature: parse_temperature(sections[0]),
+        load_average: parse_load_average(sections[1]),
+        cpu_count: sections[2].parse().ok(),
+        memory: parse_memory(sections[3], "MemTotal", "MemAvailable"),
+        swap: parse_memory(sections[3], "SwapTotal", "SwapFree"),
+        uptime: sections[4].split_whitespace().next().and_then(|uptime| uptime.parse().ok()),
+        throttling: parse_throttled(sections[5]),
+        network_interfaces: parse_network_interfaces(sections[6]),
+    })
+}
+
+/// Parses the CPU temperature, either in millidegrees from `/sys/class/thermal` or as `temp=48.3'C` from `vcgencmd`.
+///
+/// * `Input`: Command output
+/// * `Output`: Temperature in degrees Celsius
+fn parse_temperature(output: &str) -> Option<f64> {
+    if let Some(temperature) = output.strip_prefix("temp=") {
+        return temperature.trim_end_matches("'C").parse().ok();
+    }
+    output.parse::<f64>().ok().map(|millidegrees| millidegrees / 1000.0)
+}
+
+/// Parses the load averages from `/proc/loadavg`.
+///
+/// * `Input`: File contents
+/// * `Output`: Load averages over 1, 5 and 15 minutes
+fn parse_load_average(output: &str) -> Option<[f64; 3]> {
+    let mut loads = output.split_whitespace().map(|load| load.parse().ok());
+    Some([loads.next()??, loads.next()??, loads.next()??])
+}
+
+/// Parses memory or swap usage from `/proc/meminfo`, whose values are in kB.
+///
+/// * `Input`: File contents, and the keys of the total and available amounts
+/// * `Output`: Memory usage in bytes
+fn parse_memory(output: &str, total_key: &str, available_key: &str) -> Option<MemoryUsage> {
+    let value = |key: &str| -> Option<u64> {
+        let line = output.lines().find(|line| line.split(':').next() == Some(key))?;
+        let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kilobytes * 1024)
+    };
+    let total = value(total_key)?;
+    let available = value(available_key)?.min(total);
+    Some(MemoryUsage { total, used: total - available, available })
+}
+
+/// Parses the output of `vcgencmd get_throttled`, e.g. `throttled=0x50000`.
+///
+/// * `Input`: Command output
+/// * `Output`: Throttling flags, or None if `vcgencmd` is not available
+fn parse_throttled(output: &str) -> Option<ThrottleStatus> {
+    let raw = u32::from_str_radix(output.strip_prefix("throttled=0x")?, 16).ok()?;
+    let bit = |index: u32| raw & (1 << index) != 0;
+    Some(ThrottleStatus {
+        raw,
+        under_voltage: bit(0),
+        frequency_capped: bit(1),
+        throttled: bit(2),
+        soft_temperature_limit: bit(3),
+        under_voltage_occurred: bit(16),
+        frequency_capped_occurred: bit(17),
+        throttled_occurred: bit(18),
+        soft_temperature_limit_occurred: bit(19),
+    })
+}
+
+/// Parses the network interfaces and their addresses from `ip -o addr show`.
+///
+/// * `Input`: Command output, one address per line
+/// * `Output`: Network interfaces in the order they are listed
+fn parse_network_interfaces(output: &str) -> Vec<NetworkInterface> {
+    let mut interfaces: Vec<NetworkInterface> = vec![];
+    for line in output.lines() {
+        // e.g. "2: eth0    inet 192.168.1.5/24 brd 192.168.1.255 scope global eth0"
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() < 4 || (columns[2] != "inet" && columns[2] != "inet6") {
+            continue;
+        }
+        let name = columns[1].trim_end_matches(':');
+        let address = columns[3].to_string();
+        match interfaces.iter_mut().find(|interface| interface.name == name) {
+            Some(interface) => interface.addresses.push(address),
+            None => interfaces.push(NetworkInterface { name: name.to_string(), addresses: vec![address] }),
+        }
+    }
+    interfaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_temperature_reads_both_formats() {
+        assert_eq!(parse_temperature("48312"), Some(48.312));
+        assert_eq!(parse_temperature("temp=48.3'C"), Some(48.3));
+        assert_eq!(parse_temperature(""), None);
+    }
+
+    #[test]
+    fn parse_load_average_reads_the_first_three_values() {
+        assert_eq!(parse_load_average("0.52 0.58 0.59 1/273 12345"), Some([0.52, 0.58, 0.59]));
+        assert_eq!(parse_load_average("0.52 0.58"), None);
+    }
+
+    #[test]
+    fn parse_memory_converts_kilobytes_to_bytes() {
+        let meminfo = "MemTotal:        3884096 kB\nMemFree:          152344 kB\nMemAvailable:    2884096 kB\nSwapTotal:        102396 kB\nSwapFree:         102396 kB";
+        let memory = parse_memory(meminfo, "MemTotal", "MemAvailable").unwrap();
+        assert_eq!((memory.total, memory.used, memory.available), (3884096 * 1024, 1000000 * 1024, 2884096 * 1024));
+        let swap = parse_memory(meminfo, "SwapTotal", "SwapFree").unwrap();
+        assert_eq!(swap.used, 0);
+        assert!(parse_memory(meminfo, "HugePages_Total", "HugePages_Free").is_none());
+    }
+
+    #[test]
+    fn parse_throttled_reads_current_and_past_flags() {
+        let status = parse_throttled("throttled=0x50005").unwrap();
+        assert_eq!(status.raw, 0x50005);
+        assert!(status.under_voltage && status.throttled);
+        assert!(!status.frequency_capped && !status.soft_temperature_limit);
+        assert!(status.under_voltage_occurred && status.throttled_occurred);
+        assert!(!status.frequency_capped_occurred && !status.soft_temperature_limit_occurred);
+        assert!(parse_throttled("").is_none());
+    }
+
+    #[test]
+    fn parse_network_interfaces_groups_addresses_by_interface() {
+        let output = "1: lo    inet 127.0.0.1/8 scope host lo\n\
+                      2: eth0    inet 192.168.1.5/24 brd 192.168.1.255 scope global eth0\n\
+                      2: eth0    inet6 fe80::1/64 scope link\n\
+                      garbage";
+        let interfaces = parse_network_interfaces(output);
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[0].name, "lo");
+        assert_eq!(interfaces[1].name, "eth0");
+        assert_eq!(interfaces[1].addresses, vec!["192.168.1.5/24", "fe80::1/64"]);
+    }
+}
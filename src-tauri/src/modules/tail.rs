@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    sync::{mpsc::{self, Receiver, RecvTimeoutError, Sender}, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use ssh2::{ErrorCode, Session, Sftp};
+use tauri::{command, AppHandle, Manager, State};
+
+use super::ssh_connection::{ensure_in_sandbox, get_remote_dirs_and_session, run_remote_command, shell_quote};
+
+/// Followed files, shared between the commands and the threads following them.
+#[derive(Default)]
+pub struct FileTails(Mutex<OpenTails>);
+
+/// Followed files, keyed by ID, with the user who is following each one and the sender stopping it.
+#[derive(Default)]
+struct OpenTails {
+    last_id: u32,
+    tails: HashMap<u32, (String, Sender<()>)>,
+}
+
+/// Struct to represent the start of following a file.
+#[derive(Debug, Serialize)]
+pub struct TailStart {
+    pub id: u32,
+    /// Last lines of the file, oldest first
+    pub lines: Vec<String>,
+}
+
+/// Struct to represent lines appended to a followed file, emitted as a "tail-output" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TailOutput {
+    pub id: u32,
+    pub lines: Vec<String>,
+    /// Whether the file shrank or was replaced, e.g. by log rotation, and is followed again from its start
+    pub truncated: bool,
+}
+
+/// Struct to represent the end of following a file, emitted as a "tail-end" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TailEnd {
+    pub id: u32,
+    /// Error that stopped following the file, or None if it was stopped by the user
+    pub error: Option<String>,
+}
+
+const DEFAULT_TAIL_LINES: u32 = 100;
+const MAX_TAIL_LINES: u32 = 10000;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const READ_CHUNK_SIZE: u64 = 64 * 1024;
+/// Limits how much of a file is read for its last lines, and how much is read per poll when it grows quickly
+const MAX_READ_SIZE: u64 = 4 * 1024 * 1024;
+/// Data without a newline is emitted as a line once it grows past this, so binary files or very long lines are not buffered forever
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+/// SFTP status code for a file that does not exist
+const SFTP_NO_SUCH_FILE: i32 = 2;
+
+//================================================================================================
+//                              Commands for following files
+//================================================================================================
+
+/// Command to get the last lines of a file and keep following it, polling its size over SFTP and its inode with `stat`, as `tail -F` does.
+/// Complete lines appended to the file are emitted as "tail-output" events until following is stopped,
+/// and a "tail-end" event is emitted when it stops. A file that is missing for a while, e.g. during log rotation, is waited for.
+///
+/// * `Input`: User's name, current path, file name, number of lines (default 100), app handle for emitting events, and the followed files
+/// * `Output`: ID of the followed file and its last lines
+#[command]
+pub async fn tail_file(user_name: String, current_path: Vec<String>, file_name: String, lines: Option<u32>, app_handle: AppHandle, tails: State<'_, FileTails>) -> Result<TailStart, String> {
+    let (mut session, remote_dir, current_remote_dir) = get_remote_dirs_and_session(user_name.clone(), current_path).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+    ensure_in_sandbox(&sftp, &remote_dir, &remote_file_path)?;
+
+    let stat = sftp.stat(Path::new(&remote_file_path)).map_err(|e| format!("Failed to stat remote file '{}': {}", remote_file_path, e))?;
+    if !stat.is_file() {
+        return Err(format!("'{}' is not a regular file", file_name));
+    }
+    let lines = lines.unwrap_or(DEFAULT_TAIL_LINES).clamp(1, MAX_TAIL_LINES);
+    let (last_lines, offset) = read_last_lines(&sftp, &remote_file_path, stat.size.unwrap_or(0), lines as usize)?;
+    let identity = file_identity(&mut session, &remote_file_path);
+
+    let (sender, receiver) = mpsc::channel();
+    let id = {
+        let mut tails = tails.0.lock().map_err(|_| "Followed files are poisoned".to_string())?;
+        tails.last_id += 1;
+        let id = tails.last_id;
+        tails.tails.insert(id, (user_name.to_lowercase(), sender));
+        id
+    };
+
+    thread::spawn(move || {
+        let error = follow_file(id, &mut session, &sftp, &remote_file_path, (offset, identity), receiver, &app_handle).err();
+        if let Ok(mut tails) = app_handle.state::<FileTails>().0.lock() {
+            tails.tails.remove(&id);
+        }
+        app_handle.emit_all("tail-end", TailEnd { id, error }).unwrap();
+    });
+
+    Ok(TailStart { id, lines: last_lines })
+}
+
+/// Command to stop following a file.
+///
+/// * `Input`: User's name, ID of the followed file, and the followed files
+/// * `Output`: None
+#[command]
+pub async fn stop_tail_file(user_name: String, id: u32, tails: State<'_, FileTails>) -> Result<(), String> {
+    let mut tails = tails.0.lock().map_err(|_| "Followed files are poisoned".to_string())?;
+    let is_owner = tails.tails.get(&id).map(|(owner, _)| owner.eq_ignore_ascii_case(&user_name)).unwrap_or(false);
+    if !is_owner {
+        return Err(format!("File {} is not being followed", id));
+    }
+    if let Some((_, sender)) = tails.tails.remove(&id) {
+        sender.send(()).ok();
+    }
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for following files
+//================================================================================================
+
+/// Reads the last complete lines of a remote file, reading backwards from its end in chunks.
+///
+/// * `Input`: SFTP session, remote file path, file size, and number of lines
+/// * `Output`: Last lines, oldest first, and the offset following the last complete line
+fn read_last_lines(sftp: &Sftp, remote_file_path: &str, size: u64, lines: usize) -> Result<(Vec<String>, u64), String> {
+    let mut remote_file = sftp.open(Path::new(remote_file_path)).map_err(|e| format!("Failed to open file '{}': {}", remote_file_path, e))?;
+
+    // One more newline than lines is needed, since the first chunk read may start mid-line
+    let mut start = size;
+    let mut bytes = vec![];
+    while start > 0 && (size - start) < MAX_READ_SIZE && bytes.iter().filter(|byte| **byte == b'\n').count() <= lines {
+        let chunk_size = READ_CHUNK_SIZE.min(start);
+        start -= chunk_size;
+        remote_file.seek(SeekFrom::Start(start)).map_err(|e| format!("Failed to seek in file '{}': {}", remote_file_path, e))?;
+        let mut chunk = Vec::with_capacity(chunk_size as usize);
+        remote_file.by_ref().take(chunk_size).read_to_end(&mut chunk)
+            .map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
+        chunk.extend_from_slice(&bytes);
+        bytes = chunk;
+    }
+
+    let complete = complete_lines_length(&bytes);
+    let mut last_lines: Vec<String> = String::from_utf8_lossy(&bytes[..complete]).lines().map(|line| line.to_string()).collect();
+    if start > 0 && !last_lines.is_empty() {
+        last_lines.remove(0);
+    }
+    let skip = last_lines.len().saturating_sub(lines);
+    Ok((last_lines.split_off(skip), start + complete as u64))
+}
+
+/// Polls a remote file for appended data until following is stopped, emitting complete lines as "tail-output" events.
+/// The file is followed again from its start when it shrinks, or when its inode changes because it was replaced.
+///
+/// * `Input`: ID of the followed file, SSH and SFTP sessions, remote file path, offset to follow from with the file's identity there, stop receiver, and app handle for emitting events
+/// * `Output`: None, or an error if the file could no longer be read
+fn follow_file(id: u32, session: &mut Session, sftp: &Sftp, remote_file_path: &str, start: (u64, Option<String>), receiver: Receiver<()>, app_handle: &AppHandle) -> Result<(), String> {
+    let (mut offset, mut identity) = start;
+    let path = Path::new(remote_file_path);
+    let mut pending = vec![];
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let size = match sftp.stat(path) {
+            Ok(stat) => stat.size.unwrap_or(0),
+            Err(e) if e.code() == ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => continue,
+            Err(e) => return Err(format!("Failed to stat remote file '{}': {}", remote_file_path, e)),
+        };
+        // A rotated file may already be larger than the offset reached in the old one
+        let current_identity = file_identity(session, remote_file_path);
+        let replaced = matches!((&identity, &current_identity), (Some(previous), Some(current)) if previous != current);
+        if current_identity.is_some() {
+            identity = current_identity;
+        }
+        let truncated = size < offset || replaced;
+        if truncated {
+            offset = 0;
+            pending.clear();
+        } else if size == offset {
+            continue;
+        }
+
+        let mut remote_file = match sftp.open(path) {
+            Ok(remote_file) => remote_file,
+            Err(e) if e.code() == ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => continue,
+            Err(e) => return Err(format!("Failed to open file '{}': {}", remote_file_path, e)),
+        };
+        remote_file.seek(SeekFrom::Start(offset)).map_err(|e| format!("Failed to seek in file '{}': {}", remote_file_path, e))?;
+        let read = remote_file.take(MAX_READ_SIZE).read_to_end(&mut pending)
+            .map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
+        offset += read as u64;
+
+        // A line still being written is kept until its newline arrives, unless it has grown too long to keep
+        let complete = match complete_lines_length(&pending) {
+            complete if pending.len() - complete > MAX_LINE_LENGTH => pending.len(),
+            complete => complete,
+        };
+        let lines: Vec<String> = String::from_utf8_lossy(&pending[..complete]).lines().map(|line| line.to_string()).collect();
+        pending.drain(..complete);
+        if !lines.is_empty() || truncated {
+            app_handle.emit_all("tail-output", TailOutput { id, lines, truncated }).unwrap();
+        }
+    }
+}
+
+/// Gets the device and inode of a remote file with `stat`, since SFTP does not report them.
+///
+/// * `Input`: SSH session and remote file path
+/// * `Output`: Device and inode as `device:inode`, or None if the file is missing or `stat` is unavailable
+fn file_identity(session: &mut Session, remote_file_path: &str) -> Option<String> {
+    let command = format!("stat -L -c %d:%i -- {} 2>/dev/null", shell_quote(remote_file_path));
+    match run_remote_command(session, &command) {
+        Ok((output, 0)) => Some(output.trim().to_string()).filter(|identity| !identity.is_empty()),
+        _ => None,
+    }
+}
+
+/// Gets the length of the part of a buffer made of complete lines.
+///
+/// * `Input`: Buffer
+/// * `Output`: Length up to and including the last newline
+fn complete_lines_length(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|byte| *byte == b'\n').map(|position| position + 1).unwrap_or(0)
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    sync::{mpsc::{self, Receiver, Sender, TryRecvError}, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use ssh2::{Channel, Session};
+use tauri::{command, AppHandle, Manager, State};
+
+use super::{ssh_connection::get_ssh_session, users::require_admin};
+
+/// Open terminals, shared between the commands and the threads driving them.
+#[derive(Default)]
+pub struct TerminalSessions(Mutex<OpenTerminals>);
+
+/// Open terminals, keyed by ID, with the user who opened each one and the sender for its input.
+#[derive(Default)]
+struct OpenTerminals {
+    last_id: u32,
+    terminals: HashMap<u32, (String, Sender<TerminalInput>)>,
+}
+
+/// Input sent from the commands to the thread driving a terminal.
+enum TerminalInput {
+    Data(Vec<u8>),
+    Resize(u32, u32),
+    Close,
+}
+
+/// Struct to represent output of a terminal, emitted as a "terminal-output" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalOutput {
+    pub id: u32,
+    pub data: String,
+}
+
+/// Struct to represent the end of a terminal, emitted as a "terminal-exit" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalExit {
+    pub id: u32,
+    /// Exit status of the shell, or None if the connection was lost
+    pub exit_status: Option<i32>,
+}
+
+const TERMINAL_TYPE: &str = "xterm-256color";
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+//================================================================================================
+//                              Commands for terminals
+//================================================================================================
+
+/// Command to open an interactive shell on the Raspberry Pi. Restricted to admin users.
+/// Output is emitted as "terminal-output" events, and a "terminal-exit" event is emitted when the shell ends.
+///
+/// * `Input`: User's name, terminal width and height in characters, app handle for emitting events, and the open terminals
+/// * `Output`: ID of the terminal
+#[command]
+pub async fn open_terminal(user_name: String, cols: u32, rows: u32, app_handle: AppHandle, terminals: State<'_, TerminalSessions>) -> Result<u32, String> {
+    require_admin(&user_name)?;
+    let session = get_ssh_session().await?;
+    let mut channel = session.channel_session().map_err(|e| format!("Failed to open channel: {}", e))?;
+    channel.request_pty(TERMINAL_TYPE, None, Some((cols, rows, 0, 0))).map_err(|e| format!("Failed to request terminal: {}", e))?;
+    channel.shell().map_err(|e| format!("Failed to start shell: {}", e))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let id = {
+        let mut terminals = terminals.0.lock().map_err(|_| "Terminal sessions are poisoned".to_string())?;
+        terminals.last_id += 1;
+        let id = terminals.last_id;
+        terminals.terminals.insert(id, (user_name.to_lowercase(), sender));
+        id
+    };
+
+    thread::spawn(move || {
+        let exit_status = run_terminal(id, &session, &mut channel, receiver, &app_handle);
+        if let Ok(mut terminals) = app_handle.state::<TerminalSessions>().0.lock() {
+            terminals.terminals.remove(&id);
+        }
+        app_handle.emit_all("terminal-exit", TerminalExit { id, exit_status }).unwrap();
+    });
+
+    Ok(id)
+}
+
+/// Command to send keystrokes to a terminal.
+///
+/// * `Input`: User's name, terminal ID, input, and the open terminals
+/// * `Output`: None
+#[command]
+pub async fn write_terminal(user_name: String, id: u32, data: String, terminals: State<'_, TerminalSessions>) -> Result<(), String> {
+    send_terminal_input(&user_name, id, TerminalInput::Data(data.into_bytes()), &terminals)
+}
+
+/// Command to change the size of a terminal.
+///
+/// * `Input`: User's name, terminal ID, new width and height in characters, and the open terminals
+/// * `Output`: None
+#[command]
+pub async fn resize_terminal(user_name: String, id: u32, cols: u32, rows: u32, terminals: State<'_, TerminalSessions>) -> Result<(), String> {
+    send_terminal_input(&user_name, id, TerminalInput::Resize(cols, rows), &terminals)
+}
+
+/// Command to close a terminal, ending its shell.
+///
+/// * `Input`: User's name, terminal ID, and the open terminals
+/// * `Output`: None
+#[command]
+pub async fn close_terminal(user_name: String, id: u32, terminals: State<'_, TerminalSessions>) -> Result<(), String> {
+    send_terminal_input(&user_name, id, TerminalInput::Close, &terminals)
+}
+
+//================================================================================================
+//                              Helper functions for terminals
+//================================================================================================
+
+/// Sends input to the thread driving a terminal, after checking the user may use it.
+///
+/// * `Input`: User's name, terminal ID, input, and the open terminals
+/// * `Output`: None
+fn send_terminal_input(user_name: &str, id: u32, input: TerminalInput, terminals: &TerminalSessions) -> Result<(), String> {
+    require_admin(user_name)?;
+    let terminals = terminals.0.lock().map_err(|_| "Terminal sessions are poisoned".to_string())?;
+    let (owner, sender) = terminals.terminals.get(&id)
+        .filter(|(owner, _)| owner.eq_ignore_ascii_case(user_name))
+        .ok_or_else(|| format!("Terminal {} is not open", id))?;
+    sender.send(input).map_err(|_| format!("Terminal {} of '{}' has already closed", id, owner))
+}
+
+/// Relays a terminal's output as events and its input to the shell until either side closes it.
+/// The session is switched to non-blocking mode so reading does not hold up input.
+///
+/// * `Input`: Terminal ID, SSH session, shell channel, input receiver, and app handle for emitting events
+/// * `Output`: Exit status of the shell, or None if the connection was lost
+fn run_terminal(id: u32, session: &Session, channel: &mut Channel, receiver: Receiver<TerminalInput>, app_handle: &AppHandle) -> Option<i32> {
+    let mut buffer = [0; 8192];
+    let mut pending = vec![];
+    loop {
+        session.set_blocking(false);
+        let mut idle = true;
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(read) => {
+                idle = false;
+                pending.extend_from_slice(&buffer[..read]);
+                let data = take_complete_utf8(&mut pending);
+                if !data.is_empty() {
+                    app_handle.emit_all("terminal-output", TerminalOutput { id, data }).unwrap();
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_) => return None,
+        }
+
+        session.set_blocking(true);
+        match receiver.try_recv() {
+            Ok(TerminalInput::Data(data)) => {
+                idle = false;
+                channel.write_all(&data).and_then(|_| channel.flush()).ok()?;
+            }
+            Ok(TerminalInput::Resize(cols, rows)) => {
+                idle = false;
+                channel.request_pty_size(cols, rows, None, None).ok()?;
+            }
+            Ok(TerminalInput::Close) | Err(TryRecvError::Disconnected) => {
+                channel.close().ok()?;
+                break;
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+
+        if idle {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    session.set_blocking(true);
+    channel.wait_close().ok()?;
+    channel.exit_status().ok()
+}
+
+/// Takes the longest prefix of a buffer that is complete UTF-8, leaving a character split across reads for the next read.
+/// Invalid bytes are replaced, as a terminal would show them.
+///
+/// * `Input`: Buffer of terminal output
+/// * `Output`: Decoded text
+pub fn take_complete_utf8(pending: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let data = String::from_utf8_lossy(&pending[..complete]).into_owned();
+    pending.drain(..complete);
+    data
+}
@@ -0,0 +1,190 @@
+use std::{fs, io::{Cursor, Read}, path::{Path, PathBuf}, time::SystemTime};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use exif::{In, Reader, Tag};
+use filetime::{set_file_mtime, FileTime};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, ImageFormat};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use ssh2::Sftp;
+use tauri::{api::path::cache_dir, command, AppHandle, Manager};
+
+use super::ssh_connection::{get_remote_dirs_and_session, validate_file_name};
+
+/// Struct to represent the thumbnail of an image file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Thumbnail {
+    pub name: String,
+    /// JPEG thumbnail as a data URL, usable directly as an image source
+    pub data_url: String,
+}
+
+/// Struct to represent a thumbnail that could not be made, emitted as a "thumbnail-error" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThumbnailError {
+    pub name: String,
+    pub error: String,
+}
+
+const DEFAULT_THUMBNAIL_SIZE: u32 = 128;
+const MAX_THUMBNAIL_SIZE: u32 = 512;
+const THUMBNAIL_QUALITY: u8 = 80;
+/// Bytes read from the start of an image to identify it and find its embedded EXIF thumbnail
+const HEADER_SIZE: u64 = 256 * 1024;
+/// Larger images without a usable EXIF thumbnail are skipped, since they are fetched and decoded in full
+const MAX_SOURCE_SIZE: u64 = 8 * 1024 * 1024;
+/// The least recently used cached thumbnails are removed once the cache grows past this size
+const MAX_CACHE_SIZE: u64 = 100 * 1024 * 1024;
+
+//================================================================================================
+//                              Commands for thumbnails
+//================================================================================================
+
+/// Command to get thumbnails for the image files in the current directory.
+/// JPEG, PNG and WebP images are recognized by their contents. The embedded EXIF thumbnail is used when it is large enough,
+/// otherwise the image is fetched over SFTP and scaled down in Rust. Thumbnails are cached locally by path, modification time and size.
+/// Emits a "thumbnail" event as each thumbnail becomes available, and a "thumbnail-error" event for each image that fails to load;
+/// files that are not supported images are skipped.
+///
+/// * `Input`: User's name, current path, file names, maximum thumbnail width and height (default 128), and app handle for emitting events
+/// * `Output`: Thumbnails of the supported images
+#[command]
+pub async fn get_thumbnails(user_name: String, current_path: Vec<String>, file_names: Vec<String>, size: Option<u32>, app_handle: AppHandle) -> Result<Vec<Thumbnail>, String> {
+    let (session, _, current_remote_dir) = get_remote_dirs_and_session(user_name, current_path).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    let size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE).clamp(1, MAX_THUMBNAIL_SIZE);
+    // Names are kept to entries of the current directory, which is already checked to be inside the user's directory
+    for file_name in &file_names {
+        validate_file_name(file_name)?;
+    }
+    let cache_dir = get_thumbnail_cache_dir()?;
+    evict_cached_thumbnails(&cache_dir)?;
+
+    let mut thumbnails = vec![];
+    for file_name in file_names {
+        let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+        // A file that fails to decode should not prevent the rest of the thumbnails from loading
+        let jpeg = match get_thumbnail(&sftp, &remote_file_path, size, &cache_dir) {
+            Ok(Some(jpeg)) => jpeg,
+            Ok(None) => continue,
+            Err(error) => {
+                app_handle.emit_all("thumbnail-error", ThumbnailError { name: file_name, error }).unwrap();
+                continue;
+            }
+        };
+
+        let thumbnail = Thumbnail { name: file_name, data_url: format!("data:image/jpeg;base64,{}", STANDARD.encode(jpeg)) };
+        app_handle.emit_all("thumbnail", thumbnail.clone()).unwrap();
+        thumbnails.push(thumbnail);
+    }
+
+    Ok(thumbnails)
+}
+
+//================================================================================================
+//                              Helper functions for thumbnails
+//================================================================================================
+
+/// Gets the JPEG thumbnail of a remote image, from the local cache if the image has not changed since it was generated.
+///
+/// * `Input`: SFTP session, remote file path, maximum thumbnail width and height, and cache directory
+/// * `Output`: JPEG bytes, or None if the file is not a supported image or is too large to make a thumbnail of
+fn get_thumbnail(sftp: &Sftp, remote_file_path: &str, size: u32, cache_dir: &Path) -> Result<Option<Vec<u8>>, String> {
+    let stat = sftp.stat(Path::new(remote_file_path)).map_err(|e| format!("Failed to stat '{}': {}", remote_file_path, e))?;
+    let file_size = stat.size.unwrap_or(0);
+    if !stat.is_file() {
+        return Ok(None);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\n{}\n{}\n{}", remote_file_path, stat.mtime.unwrap_or(0), file_size, size));
+    let key: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    let cache_path = cache_dir.join(format!("{}.jpg", key));
+    if let Ok(jpeg) = fs::read(&cache_path) {
+        // The modification time of a cached thumbnail records when it was last used, for eviction
+        set_file_mtime(&cache_path, FileTime::now()).ok();
+        return Ok(Some(jpeg));
+    }
+
+    let mut remote_file = sftp.open(Path::new(remote_file_path)).map_err(|e| format!("Failed to open remote file '{}': {}", remote_file_path, e))?;
+    let mut bytes = vec![];
+    (&mut remote_file).take(HEADER_SIZE).read_to_end(&mut bytes).map_err(|e| format!("Failed to read remote file '{}': {}", remote_file_path, e))?;
+    let format = match image::guess_format(&bytes) {
+        Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+        _ => return Ok(None),
+    };
+
+    let image = match embedded_thumbnail(&bytes, size).filter(|_| format == ImageFormat::Jpeg) {
+        Some(image) => image,
+        None => {
+            if file_size > MAX_SOURCE_SIZE {
+                return Ok(None);
+            }
+            remote_file.read_to_end(&mut bytes).map_err(|e| format!("Failed to read remote file '{}': {}", remote_file_path, e))?;
+            image::load_from_memory_with_format(&bytes, format).map_err(|e| format!("Failed to decode image '{}': {}", remote_file_path, e))?
+        }
+    };
+    let thumbnail = image.resize(size, size, FilterType::Triangle).to_rgb8();
+    let mut jpeg = vec![];
+    JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY)
+        .encode_image(&thumbnail)
+        .map_err(|e| format!("Failed to encode thumbnail of '{}': {}", remote_file_path, e))?;
+
+    fs::write(&cache_path, &jpeg).map_err(|e| format!("Failed to cache thumbnail at '{}': {}", cache_path.display(), e))?;
+    Ok(Some(jpeg))
+}
+
+/// Decodes the thumbnail embedded in the EXIF data of a JPEG, if it is at least as large as the requested thumbnail.
+///
+/// * `Input`: First bytes of the JPEG file and maximum thumbnail width and height
+/// * `Output`: Embedded thumbnail, or None if there is no usable one
+fn embedded_thumbnail(header: &[u8], size: u32) -> Option<image::DynamicImage> {
+    let exif = Reader::new().read_from_container(&mut Cursor::new(header)).ok()?;
+    // The thumbnail offset is relative to the start of the EXIF data
+    let offset = exif.get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+    let length = exif.get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+    let jpeg = exif.buf().get(offset..offset.checked_add(length)?)?;
+    let image = image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).ok()?;
+    if image.width().max(image.height()) < size {
+        return None;
+    }
+    Some(image)
+}
+
+/// Gets the local directory thumbnails are cached in, creating it if needed.
+///
+/// * `Output`: Thumbnail cache directory
+fn get_thumbnail_cache_dir() -> Result<PathBuf, String> {
+    let cache_dir = cache_dir().ok_or("Failed to find the cache directory")?.join("pi-interface").join("thumbnails");
+    fs::create_dir_all(&cache_dir).map_err(|e| format!("Failed to create thumbnail cache directory '{}': {}", cache_dir.display(), e))?;
+    Ok(cache_dir)
+}
+
+/// Removes the least recently used cached thumbnails once the cache is larger than `MAX_CACHE_SIZE`, down to three quarters of it.
+///
+/// * `Input`: Thumbnail cache directory
+/// * `Output`: None
+fn evict_cached_thumbnails(cache_dir: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(cache_dir).map_err(|e| format!("Failed to read thumbnail cache directory '{}': {}", cache_dir.display(), e))?;
+    let mut thumbnails: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect();
+    let mut cache_size: u64 = thumbnails.iter().map(|(_, size, _)| size).sum();
+    if cache_size <= MAX_CACHE_SIZE {
+        return Ok(());
+    }
+
+    thumbnails.sort();
+    for (_, size, path) in thumbnails {
+        if cache_size <= MAX_CACHE_SIZE / 4 * 3 {
+            break;
+        }
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove cached thumbnail '{}': {}", path.display(), e))?;
+        cache_size -= size;
+    }
+    Ok(())
+}
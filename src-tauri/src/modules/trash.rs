@@ -0,0 +1,321 @@
+use std::{env, io::{Read, Write}, path::Path};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use ssh2::{Session, Sftp};
+use tauri::{command, State};
+
+use super::{
+    ssh_connection::{get_home_directory, get_remote_dirs_and_session, get_ssh_session, recursive_delete},
+    storage::StorageCache,
+};
+
+/// Struct to represent an item in a user's recycle bin.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    pub deleted_at: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+//================================================================================================
+//                              Commands for the recycle bin
+//================================================================================================
+
+/// Command to move files or folders in the current directory to the user's recycle bin.
+/// Items older than the retention period are purged before the new items are added.
+///
+/// * `Input`: User's name, current path, file names, and the storage cache
+/// * `Output`: None
+#[command]
+pub async fn delete_files(user_name: String, current_path: Vec<String>, file_names: Vec<String>, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
+    let (mut session, _, current_remote_dir) = get_remote_dirs_and_session(user_name.clone(), current_path.clone()).await?;
+    let trash_dir = verify_trash_directory(&mut session, &user_name)?;
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    purge_expired_entries(&sftp, &trash_dir)?;
+
+    let deleted_at = Utc::now();
+    for (index, file_name) in file_names.iter().enumerate() {
+        let remote_file_path = format!("{}/{}", current_remote_dir, file_name);
+        let original_path = if current_path.is_empty() {
+            file_name.clone()
+        } else {
+            format!("{}/{}", current_path.join("/"), file_name)
+        };
+        let id = format!("{}-{}", deleted_at.format("%Y%m%d%H%M%S%3f"), index);
+        move_to_trash(&sftp, &trash_dir, &remote_file_path, original_path, id, deleted_at)?;
+    }
+
+    Ok(())
+}
+
+/// Command to list the items in the user's recycle bin, newest first.
+/// Items older than the retention period are purged before listing.
+///
+/// * `Input`: User's name
+/// * `Output`: List of items in the recycle bin
+#[command]
+pub async fn list_trash(user_name: String) -> Result<Vec<TrashEntry>, String> {
+    let mut session = get_ssh_session().await?;
+    let trash_dir = verify_trash_directory(&mut session, &user_name)?;
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+    purge_expired_entries(&sftp, &trash_dir)?;
+
+    let mut entries = read_trash_entries(&sftp, &trash_dir)?;
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+
+    Ok(entries)
+}
+
+/// Command to restore items from the recycle bin to their original location.
+/// Missing parent folders are recreated. Fails if an item already exists at the original location.
+///
+/// * `Input`: User's name, IDs of the items to restore, and the storage cache
+/// * `Output`: None
+#[command]
+pub async fn restore_from_trash(user_name: String, ids: Vec<String>, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
+    let (mut session, remote_dir, _) = get_remote_dirs_and_session(user_name.clone(), vec![]).await?;
+    let trash_dir = verify_trash_directory(&mut session, &user_name)?;
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    for id in ids {
+        let entry = read_trash_entry(&sftp, &trash_dir, &id)?;
+        let restore_path = format!("{}/{}", remote_dir, entry.original_path);
+        if sftp.stat(Path::new(&restore_path)).is_ok() {
+            return Err(format!("Cannot restore '{}': an item with that name already exists", entry.original_path));
+        }
+
+        // Recreate any parent folders that were deleted since
+        let mut parent_dir = remote_dir.clone();
+        if let Some((parents, _)) = entry.original_path.rsplit_once('/') {
+            for folder in parents.split('/') {
+                parent_dir = format!("{}/{}", parent_dir, folder);
+                if sftp.stat(Path::new(&parent_dir)).is_err() {
+                    sftp.mkdir(Path::new(&parent_dir), 0o755)
+                        .map_err(|e| format!("Failed to create folder '{}': {}", parent_dir, e))?;
+                }
+            }
+        }
+
+        let trash_item_path = format!("{}/{}", trash_dir, entry.id);
+        sftp.rename(Path::new(&trash_item_path), Path::new(&restore_path), None)
+            .map_err(|e| format!("Failed to restore '{}': {}", entry.original_path, e))?;
+        remove_trash_metadata(&sftp, &trash_dir, &entry.id)?;
+    }
+
+    Ok(())
+}
+
+/// Command to permanently delete items from the recycle bin.
+///
+/// * `Input`: User's name, optional IDs of the items to delete (all items if omitted)
+/// * `Output`: None
+#[command]
+pub async fn empty_trash(user_name: String, ids: Option<Vec<String>>) -> Result<(), String> {
+    let mut session = get_ssh_session().await?;
+    let trash_dir = verify_trash_directory(&mut session, &user_name)?;
+
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let ids = match ids {
+        Some(ids) => ids,
+        None => read_trash_entries(&sftp, &trash_dir)?.into_iter().map(|entry| entry.id).collect(),
+    };
+    for id in ids {
+        delete_trash_item(&sftp, &trash_dir, &id)?;
+    }
+
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for the recycle bin
+//================================================================================================
+
+/// Verifies the existence of the user's recycle bin on the Raspberry Pi, creating it if needed.
+/// The recycle bin lives outside the user's directory so it is not shown in their listings.
+///
+/// * `Input`: SSH session and user's name
+/// * `Output`: Recycle bin directory
+pub fn verify_trash_directory(session: &mut Session, user_name: &str) -> Result<String, String> {
+    let home_dir = get_home_directory(session)?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let mut trash_dir = format!("{}/pi-interface", home_dir);
+    for folder in [".trash", user_name] {
+        trash_dir = format!("{}/{}", trash_dir, folder);
+        if sftp.stat(Path::new(&trash_dir)).is_err() {
+            sftp.mkdir(Path::new(&trash_dir), 0o755).map_err(|e| format!("Failed to create recycle bin directory {}: {}", trash_dir, e))?;
+        }
+    }
+
+    Ok(trash_dir)
+}
+
+/// Moves a file or folder to the user's recycle bin and records where it came from.
+///
+/// * `Input`: SFTP session, recycle bin directory, remote path, path relative to the user's directory, item ID, and deletion time
+/// * `Output`: None
+pub fn move_to_trash(sftp: &Sftp, trash_dir: &str, remote_file_path: &str, original_path: String, id: String, deleted_at: DateTime<Utc>) -> Result<(), String> {
+    let path = Path::new(remote_file_path);
+    // Symbolic links are trashed as links, whether or not their target exists
+    let stat = sftp.lstat(path).map_err(|e| format!("Failed to stat '{}': {}", remote_file_path, e))?;
+
+    let entry = TrashEntry {
+        id,
+        name: original_path.rsplit('/').next().unwrap_or(&original_path).to_string(),
+        original_path,
+        deleted_at: deleted_at.to_rfc3339(),
+        is_dir: stat.is_dir(),
+        size: stat.size.unwrap_or(0),
+    };
+
+    // The metadata is written first, so an item is never in the recycle bin without a record of where to restore it
+    write_trash_entry(sftp, trash_dir, &entry)?;
+    let trash_item_path = format!("{}/{}", trash_dir, entry.id);
+    if let Err(e) = sftp.rename(path, Path::new(&trash_item_path), None) {
+        remove_trash_metadata(sftp, trash_dir, &entry.id).ok();
+        return Err(format!("Failed to move '{}' to the recycle bin: {}", remote_file_path, e));
+    }
+    Ok(())
+}
+
+/// Gets the number of days items are kept in the recycle bin.
+/// Read from `VITE_TRASH_RETENTION_DAYS`, defaulting to 30 days.
+///
+/// * `Output`: Retention period in days
+fn get_retention_days() -> i64 {
+    dotenv::dotenv().ok();
+    env::var("VITE_TRASH_RETENTION_DAYS").ok()
+        .and_then(|days| days.trim().parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+/// Permanently deletes items that have been in the recycle bin longer than the retention period.
+///
+/// * `Input`: SFTP session and recycle bin directory
+/// * `Output`: None
+pub fn purge_expired_entries(sftp: &Sftp, trash_dir: &str) -> Result<(), String> {
+    let cutoff = Utc::now() - Duration::days(get_retention_days());
+    for entry in read_trash_entries(sftp, trash_dir)? {
+        let expired = DateTime::parse_from_rfc3339(&entry.deleted_at)
+            .map(|deleted_at| deleted_at < cutoff)
+            .unwrap_or(false);
+        if expired {
+            delete_trash_item(sftp, trash_dir, &entry.id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the metadata of every item in the recycle bin.
+/// Metadata that cannot be read or parsed is logged and skipped, so one bad item does not hide the others.
+///
+/// * `Input`: SFTP session and recycle bin directory
+/// * `Output`: List of items in the recycle bin
+fn read_trash_entries(sftp: &Sftp, trash_dir: &str) -> Result<Vec<TrashEntry>, String> {
+    let entries = sftp.readdir(Path::new(trash_dir)).map_err(|e| format!("Failed to read directory {}: {}", trash_dir, e))?;
+    let mut trash_entries = vec![];
+    for (path, _) in entries {
+        if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            if let Some(id) = path.file_stem() {
+                match read_trash_entry(sftp, trash_dir, &id.to_string_lossy()) {
+                    Ok(entry) => trash_entries.push(entry),
+                    Err(e) => eprintln!("Skipping recycle bin item: {}", e),
+                }
+            }
+        }
+    }
+    Ok(trash_entries)
+}
+
+/// Reads the metadata of a single item in the recycle bin.
+///
+/// * `Input`: SFTP session, recycle bin directory, and item ID
+/// * `Output`: Recycle bin item
+fn read_trash_entry(sftp: &Sftp, trash_dir: &str, id: &str) -> Result<TrashEntry, String> {
+    validate_trash_id(id)?;
+    let metadata_path = format!("{}/{}.json", trash_dir, id);
+    let mut metadata_file = sftp.open(Path::new(&metadata_path))
+        .map_err(|e| format!("Failed to open recycle bin metadata '{}': {}", metadata_path, e))?;
+    let mut contents = String::new();
+    metadata_file.read_to_string(&mut contents)
+        .map_err(|e| format!("Failed to read recycle bin metadata '{}': {}", metadata_path, e))?;
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse recycle bin metadata '{}': {}", metadata_path, e))
+}
+
+/// Writes the metadata of an item next to it in the recycle bin.
+///
+/// * `Input`: SFTP session, recycle bin directory, and recycle bin item
+/// * `Output`: None
+fn write_trash_entry(sftp: &Sftp, trash_dir: &str, entry: &TrashEntry) -> Result<(), String> {
+    let metadata_path = format!("{}/{}.json", trash_dir, entry.id);
+    let contents = serde_json::to_string(entry).map_err(|e| format!("Failed to serialize recycle bin metadata: {}", e))?;
+    let mut metadata_file = sftp.create(Path::new(&metadata_path))
+        .map_err(|e| format!("Failed to create recycle bin metadata '{}': {}", metadata_path, e))?;
+    metadata_file.write_all(contents.as_bytes())
+        .map_err(|e| format!("Failed to write recycle bin metadata '{}': {}", metadata_path, e))?;
+    Ok(())
+}
+
+/// Removes the metadata file of an item in the recycle bin.
+///
+/// * `Input`: SFTP session, recycle bin directory, and item ID
+/// * `Output`: None
+fn remove_trash_metadata(sftp: &Sftp, trash_dir: &str, id: &str) -> Result<(), String> {
+    let metadata_path = format!("{}/{}.json", trash_dir, id);
+    sftp.unlink(Path::new(&metadata_path)).map_err(|e| format!("Failed to delete recycle bin metadata '{}': {}", metadata_path, e))
+}
+
+/// Permanently deletes an item and its metadata from the recycle bin.
+///
+/// * `Input`: SFTP session, recycle bin directory, and item ID
+/// * `Output`: None
+fn delete_trash_item(sftp: &Sftp, trash_dir: &str, id: &str) -> Result<(), String> {
+    validate_trash_id(id)?;
+    let trash_item_path = format!("{}/{}", trash_dir, id);
+    let path = Path::new(&trash_item_path);
+    if let Ok(stat) = sftp.lstat(path) {
+        if stat.is_dir() {
+            recursive_delete(sftp, path)?;
+        } else {
+            sftp.unlink(path).map_err(|e| format!("Failed to delete file '{}': {}", trash_item_path, e))?;
+        }
+    }
+    remove_trash_metadata(sftp, trash_dir, id)
+}
+
+/// Ensures an item ID from the frontend cannot point outside the recycle bin.
+///
+/// * `Input`: Item ID
+/// * `Output`: None
+fn validate_trash_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return Err(format!("Invalid recycle bin item ID '{}'", id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_trash_id_only_accepts_generated_ids() {
+        assert!(validate_trash_id("20240102030405123").is_ok());
+        assert!(validate_trash_id("20240102030405123-2").is_ok());
+        assert!(validate_trash_id("").is_err());
+        assert!(validate_trash_id("../files").is_err());
+        assert!(validate_trash_id("123/456").is_err());
+    }
+}
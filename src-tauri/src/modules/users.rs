@@ -0,0 +1,62 @@
+use std::env;
+
+use serde::Deserialize;
+
+/// Struct to represent a user configured in `VITE_USERS`.
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub name: String,
+    /// Either "admin" or "user"; users without a role are regular users
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Storage limit in GB; users without a limit have unlimited storage
+    #[serde(default)]
+    pub storage_limit: f64,
+    /// Names of the actions in `VITE_ACTIONS` the user may run; admins may run all of them
+    #[serde(default)]
+    pub actions: Vec<String>,
+}
+
+impl User {
+    /// Checks whether the user has the admin role.
+    ///
+    /// * `Output`: Whether the user is an admin
+    pub fn is_admin(&self) -> bool {
+        self.role.as_deref() == Some("admin")
+    }
+}
+
+//================================================================================================
+//                              Helper functions for users
+//================================================================================================
+
+/// Loads the users from the `VITE_USERS` JSON array in the .env file.
+///
+/// * `Output`: List of configured users
+pub fn load_users() -> Result<Vec<User>, String> {
+    dotenv::dotenv().ok();
+    let users = env::var("VITE_USERS").map_err(|e| format!("Failed to load VITE_USERS: {}", e))?;
+    serde_json::from_str(&users).map_err(|e| format!("Failed to parse VITE_USERS: {}", e))
+}
+
+/// Finds a configured user by name, ignoring case since the frontend sends lowercase names.
+///
+/// * `Input`: User's name
+/// * `Output`: The user
+pub fn find_user(user_name: &str) -> Result<User, String> {
+    load_users()?
+        .into_iter()
+        .find(|user| user.name.eq_ignore_ascii_case(user_name))
+        .ok_or_else(|| format!("Unknown user '{}'", user_name))
+}
+
+/// Ensures a user has the admin role.
+///
+/// * `Input`: User's name
+/// * `Output`: None, or an error if the user is not an admin
+pub fn require_admin(user_name: &str) -> Result<(), String> {
+    if !find_user(user_name)?.is_admin() {
+        return Err(format!("User '{}' is not allowed to do this: admin role required", user_name));
+    }
+    Ok(())
+}
@@ -0,0 +1,435 @@
+use std::{env, io::{Read, Write}, path::Path};
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use ssh2::Sftp;
+use tauri::{command, State};
+
+use super::{
+    file_content::{decode_text, detect_text_format, read_existing_file},
+    ssh_connection::{create_remote_dir_all, ensure_in_sandbox, get_remote_dirs_and_session, normalize_relative_path},
+    storage::StorageCache,
+};
+
+/// Struct to represent a previous version of a file edited in the app.
+#[derive(Debug, Serialize)]
+pub struct FileVersion {
+    pub id: String,
+    /// ISO-8601 time the version was replaced by a newer save
+    pub saved_at: String,
+    pub size: u64,
+}
+
+/// Struct to represent one line of a diff between two versions of a file.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    /// Either "equal", "added" or "removed"
+    pub kind: String,
+    pub text: String,
+}
+
+const VERSION_ID_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+const DEFAULT_MAX_VERSIONS_PER_FILE: usize = 20;
+const DEFAULT_MAX_VERSIONS_SIZE: u64 = 100 * 1024 * 1024; // 100MB
+/// Limits the memory used by `diff_lines` to about 64MB
+const MAX_DIFF_CELLS: usize = 16 * 1024 * 1024;
+
+//================================================================================================
+//                              Commands for file version history
+//================================================================================================
+
+/// Command to list the previous versions of a file, newest first.
+///
+/// * `Input`: User's name, current path, file name
+/// * `Output`: List of previous versions of the file
+#[command]
+pub async fn list_file_versions(user_name: String, current_path: Vec<String>, file_name: String) -> Result<Vec<FileVersion>, String> {
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, current_path.clone()).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_path = resolve_relative_file_path(&sftp, &remote_dir, &current_path, &file_name)?;
+    let file_versions_dir = format!("{}/{}", get_versions_dir(&remote_dir), relative_path);
+    let mut versions = read_versions(&sftp, &file_versions_dir)?;
+    versions.reverse();
+
+    Ok(versions)
+}
+
+/// Command to read the content of a previous version of a file.
+///
+/// * `Input`: User's name, current path, file name, version ID
+/// * `Output`: Content of the version as a string
+#[command]
+pub async fn read_file_version(user_name: String, current_path: Vec<String>, file_name: String, version_id: String) -> Result<String, String> {
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, current_path.clone()).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_path = resolve_relative_file_path(&sftp, &remote_dir, &current_path, &file_name)?;
+    let file_versions_dir = format!("{}/{}", get_versions_dir(&remote_dir), relative_path);
+    read_version(&sftp, &file_versions_dir, &version_id)
+}
+
+/// Command to diff two versions of a file line by line.
+///
+/// * `Input`: User's name, current path, file name, and the version IDs to compare (the current file if omitted)
+/// * `Output`: Lines of the diff from the first version to the second
+#[command]
+pub async fn diff_file_versions(user_name: String, current_path: Vec<String>, file_name: String, from_version: Option<String>, to_version: Option<String>) -> Result<Vec<DiffLine>, String> {
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, current_path.clone()).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_path = resolve_relative_file_path(&sftp, &remote_dir, &current_path, &file_name)?;
+    let file_versions_dir = format!("{}/{}", get_versions_dir(&remote_dir), relative_path);
+    let current_file_path = format!("{}/{}", remote_dir, relative_path);
+
+    let from = match from_version {
+        Some(version_id) => read_version(&sftp, &file_versions_dir, &version_id)?,
+        None => read_text(&sftp, &current_file_path)?,
+    };
+    let to = match to_version {
+        Some(version_id) => read_version(&sftp, &file_versions_dir, &version_id)?,
+        None => read_text(&sftp, &current_file_path)?,
+    };
+
+    diff_lines(&from, &to)
+}
+
+/// Command to restore a previous version of a file.
+/// The current content is kept as a new version so the restore can itself be undone.
+///
+/// * `Input`: User's name, current path, file name, version ID, and the storage cache
+/// * `Output`: None
+#[command]
+pub async fn restore_file_version(user_name: String, current_path: Vec<String>, file_name: String, version_id: String, storage_cache: State<'_, StorageCache>) -> Result<(), String> {
+    storage_cache.invalidate(&user_name);
+    let (session, remote_dir, _) = get_remote_dirs_and_session(user_name, current_path.clone()).await?;
+    let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+
+    let relative_path = resolve_relative_file_path(&sftp, &remote_dir, &current_path, &file_name)?;
+    let file_versions_dir = format!("{}/{}", get_versions_dir(&remote_dir), relative_path);
+    validate_version_id(&version_id)?;
+    let content = read_bytes(&sftp, &format!("{}/{}", file_versions_dir, version_id))?;
+
+    let remote_file_path = format!("{}/{}", remote_dir, relative_path);
+    if let Some((_, current)) = read_existing_file(&sftp, Path::new(&remote_file_path))? {
+        store_version(&sftp, &remote_dir, &relative_path, &current)?;
+    }
+
+    let mut remote_file = sftp.create(Path::new(&remote_file_path))
+        .map_err(|e| format!("Failed to create file '{}': {}", remote_file_path, e))?;
+    remote_file.write_all(&content).map_err(|e| format!("Failed to write to file '{}': {}", remote_file_path, e))?;
+
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for file version history
+//================================================================================================
+
+/// Stores the current content of a file in the user's versions store before it is overwritten,
+/// then prunes the oldest versions of that file beyond the per-file cap, and the oldest versions of any file beyond the total size cap.
+///
+/// * `Input`: SFTP session, user's directory, path of the file relative to it, and its current content
+/// * `Output`: None
+pub fn store_version(sftp: &Sftp, remote_dir: &str, relative_path: &str, content: &[u8]) -> Result<(), String> {
+    let versions_dir = get_versions_dir(remote_dir);
+    let file_versions_dir = format!("{}/{}", versions_dir, relative_path);
+    create_remote_dir_all(sftp, &file_versions_dir)?;
+
+    let version_path = format!("{}/{}", file_versions_dir, Utc::now().format(VERSION_ID_FORMAT));
+    let mut version_file = sftp.create(Path::new(&version_path))
+        .map_err(|e| format!("Failed to create version '{}': {}", version_path, e))?;
+    version_file.write_all(content).map_err(|e| format!("Failed to write version '{}': {}", version_path, e))?;
+
+    prune_file_versions(sftp, &file_versions_dir)?;
+    prune_versions_store(sftp, &versions_dir, &version_path)
+}
+
+/// Gets the versions store of a user, which mirrors the layout of their directory.
+/// The store lives outside the user's directory so it is not shown in their listings.
+///
+/// * `Input`: User's directory
+/// * `Output`: Versions store directory
+fn get_versions_dir(remote_dir: &str) -> String {
+    match remote_dir.rsplit_once('/') {
+        Some((base_dir, user_name)) => format!("{}/.versions/{}", base_dir, user_name),
+        None => format!(".versions/{}", remote_dir),
+    }
+}
+
+/// Joins the current path and a file name into a path relative to the user's directory.
+///
+/// * `Input`: Current path and file name
+/// * `Output`: Relative file path
+pub fn relative_file_path(current_path: &[String], file_name: &str) -> String {
+    if current_path.is_empty() {
+        file_name.to_string()
+    } else {
+        format!("{}/{}", current_path.join("/"), file_name)
+    }
+}
+
+/// Resolves the path of a file relative to the user's directory, ensuring it stays inside it.
+/// The file may no longer exist, e.g. after being deleted, in which case its directory is checked instead.
+///
+/// * `Input`: SFTP session, user's directory, current path and file name
+/// * `Output`: Normalized file path relative to the user's directory
+pub fn resolve_relative_file_path(sftp: &Sftp, remote_dir: &str, current_path: &[String], file_name: &str) -> Result<String, String> {
+    let relative_path = normalize_relative_path(&relative_file_path(current_path, file_name))
+        .filter(|relative_path| !relative_path.is_empty())
+        .ok_or_else(|| format!("'{}' is outside of the user's directory", file_name))?;
+
+    let remote_file_path = format!("{}/{}", remote_dir, relative_path);
+    if sftp.lstat(Path::new(&remote_file_path)).is_ok() {
+        ensure_in_sandbox(sftp, remote_dir, &remote_file_path)?;
+    } else if let Some((parent_dir, _)) = remote_file_path.rsplit_once('/') {
+        ensure_in_sandbox(sftp, remote_dir, parent_dir)?;
+    }
+    Ok(relative_path)
+}
+
+/// Gets the maximum number of versions kept per file.
+/// Read from `VITE_MAX_VERSIONS_PER_FILE`, defaulting to 20.
+///
+/// * `Output`: Maximum number of versions
+fn get_max_versions_per_file() -> usize {
+    dotenv::dotenv().ok();
+    env::var("VITE_MAX_VERSIONS_PER_FILE").ok()
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_VERSIONS_PER_FILE)
+}
+
+/// Gets the maximum total size of a user's versions store.
+/// Read from `VITE_MAX_VERSIONS_SIZE` in bytes, defaulting to 100MB.
+///
+/// * `Output`: Maximum size in bytes
+fn get_max_versions_size() -> u64 {
+    dotenv::dotenv().ok();
+    env::var("VITE_MAX_VERSIONS_SIZE").ok()
+        .and_then(|size| size.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_VERSIONS_SIZE)
+}
+
+/// Lists the versions stored for a file, oldest first.
+///
+/// * `Input`: SFTP session and the file's versions directory
+/// * `Output`: List of versions, empty if none have been stored
+fn read_versions(sftp: &Sftp, file_versions_dir: &str) -> Result<Vec<FileVersion>, String> {
+    let entries = match sftp.readdir(Path::new(file_versions_dir)) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut versions: Vec<FileVersion> = entries.into_iter()
+        .filter(|(_, stat)| stat.is_file())
+        .filter_map(|(path, stat)| {
+            let id = path.file_name()?.to_string_lossy().to_string();
+            let saved_at = NaiveDateTime::parse_from_str(&id, VERSION_ID_FORMAT).ok()?;
+            Some(FileVersion {
+                saved_at: Utc.from_utc_datetime(&saved_at).to_rfc3339(),
+                id,
+                size: stat.size.unwrap_or(0),
+            })
+        })
+        .collect();
+    versions.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(versions)
+}
+
+/// Reads the content of a stored version.
+///
+/// * `Input`: SFTP session, the file's versions directory, and version ID
+/// * `Output`: Content of the version
+fn read_version(sftp: &Sftp, file_versions_dir: &str, version_id: &str) -> Result<String, String> {
+    validate_version_id(version_id)?;
+    read_text(sftp, &format!("{}/{}", file_versions_dir, version_id))
+}
+
+/// Ensures a version ID from the frontend cannot point outside the versions store.
+///
+/// * `Input`: Version ID
+/// * `Output`: None
+fn validate_version_id(version_id: &str) -> Result<(), String> {
+    if version_id.is_empty() || !version_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid version ID '{}'", version_id));
+    }
+    Ok(())
+}
+
+/// Reads a remote file as text, decoding it in its detected encoding.
+///
+/// * `Input`: SFTP session and remote file path
+/// * `Output`: File content with line endings normalized to `\n`
+fn read_text(sftp: &Sftp, remote_file_path: &str) -> Result<String, String> {
+    let content = read_bytes(sftp, remote_file_path)?;
+    let format = detect_text_format(&content).unwrap_or_default();
+    Ok(decode_text(&content, &format).replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+/// Reads the raw bytes of a remote file.
+///
+/// * `Input`: SFTP session and remote file path
+/// * `Output`: File content
+fn read_bytes(sftp: &Sftp, remote_file_path: &str) -> Result<Vec<u8>, String> {
+    let mut remote_file = sftp.open(Path::new(remote_file_path))
+        .map_err(|e| format!("Failed to open file '{}': {}", remote_file_path, e))?;
+    let mut content = vec![];
+    remote_file.read_to_end(&mut content).map_err(|e| format!("Failed to read file '{}': {}", remote_file_path, e))?;
+    Ok(content)
+}
+
+/// Deletes a stored version.
+///
+/// * `Input`: SFTP session and version path
+/// * `Output`: None
+fn delete_version(sftp: &Sftp, version_path: &str) -> Result<(), String> {
+    sftp.unlink(Path::new(version_path)).map_err(|e| format!("Failed to delete version '{}': {}", version_path, e))
+}
+
+/// Deletes the oldest versions of a file beyond the per-file cap.
+///
+/// * `Input`: SFTP session and the file's versions directory
+/// * `Output`: None
+fn prune_file_versions(sftp: &Sftp, file_versions_dir: &str) -> Result<(), String> {
+    let versions = read_versions(sftp, file_versions_dir)?;
+    let excess = versions.len().saturating_sub(get_max_versions_per_file().max(1));
+    for version in &versions[..excess] {
+        delete_version(sftp, &format!("{}/{}", file_versions_dir, version.id))?;
+    }
+    Ok(())
+}
+
+/// Deletes the oldest versions across the whole versions store until it fits within the total size cap.
+/// The version that was just stored is kept, and directories of files with no versions left are removed.
+///
+/// * `Input`: SFTP session, versions store directory, and path of the version just stored
+/// * `Output`: None
+fn prune_versions_store(sftp: &Sftp, versions_dir: &str, newest_version_path: &str) -> Result<(), String> {
+    // Collect (version ID, path, size) for every stored version
+    let mut all_versions = vec![];
+    let mut pending_dirs = vec![versions_dir.to_string()];
+    while let Some(dir) = pending_dirs.pop() {
+        let entries = sftp.readdir(Path::new(&dir)).map_err(|e| format!("Failed to read directory {}: {}", dir, e))?;
+        if entries.is_empty() && dir != versions_dir {
+            sftp.rmdir(Path::new(&dir)).map_err(|e| format!("Failed to remove directory {}: {}", dir, e))?;
+            continue;
+        }
+        for (path, stat) in entries {
+            if stat.is_dir() {
+                pending_dirs.push(path.to_string_lossy().to_string());
+            } else if let Some(id) = path.file_name() {
+                all_versions.push((id.to_string_lossy().to_string(), path.to_string_lossy().to_string(), stat.size.unwrap_or(0)));
+            }
+        }
+    }
+
+    let mut total_size: u64 = all_versions.iter().map(|(_, _, size)| size).sum();
+    let max_size = get_max_versions_size();
+    all_versions.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, path, size) in all_versions {
+        if total_size <= max_size {
+            break;
+        }
+        if path == newest_version_path {
+            continue;
+        }
+        delete_version(sftp, &path)?;
+        total_size -= size;
+    }
+
+    Ok(())
+}
+
+/// Computes a line diff between two texts using their longest common subsequence.
+/// Lines shared at the start and end are matched up front, and the rest is refused if the comparison table would be too large.
+///
+/// * `Input`: Old and new text
+/// * `Output`: Lines of the diff
+fn diff_lines(old: &str, new: &str) -> Result<Vec<DiffLine>, String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(old, new)| old == new).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(old, new)| old == new).count();
+    let (old_changed, new_changed) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    if old_changed.len().saturating_mul(new_changed.len()) > MAX_DIFF_CELLS {
+        return Err(format!(
+            "Files are too different to compare: {} and {} changed lines",
+            old_changed.len(),
+            new_changed.len(),
+        ));
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of old_changed[i..] and new_changed[j..]
+    let mut lcs = vec![vec![0u32; new_changed.len() + 1]; old_changed.len() + 1];
+    for i in (0..old_changed.len()).rev() {
+        for j in (0..new_changed.len()).rev() {
+            lcs[i][j] = if old_changed[i] == new_changed[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |kind: &str, text: &str| DiffLine { kind: kind.to_string(), text: text.to_string() };
+    let mut diff: Vec<DiffLine> = old[..prefix].iter().map(|text| line("equal", text)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_changed.len() && j < new_changed.len() {
+        if old_changed[i] == new_changed[j] {
+            diff.push(line("equal", old_changed[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(line("removed", old_changed[i]));
+            i += 1;
+        } else {
+            diff.push(line("added", new_changed[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old_changed[i..].iter().map(|text| line("removed", text)));
+    diff.extend(new_changed[j..].iter().map(|text| line("added", text)));
+    diff.extend(old[old.len() - suffix..].iter().map(|text| line("equal", text)));
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_and_texts(diff: &[DiffLine]) -> Vec<(&str, &str)> {
+        diff.iter().map(|line| (line.kind.as_str(), line.text.as_str())).collect()
+    }
+
+    #[test]
+    fn diff_lines_marks_identical_texts_as_equal() {
+        let diff = diff_lines("a\nb\n", "a\nb\n").unwrap();
+        assert_eq!(kinds_and_texts(&diff), vec![("equal", "a"), ("equal", "b")]);
+    }
+
+    #[test]
+    fn diff_lines_finds_added_removed_and_changed_lines() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nc\nx\nd\ne").unwrap();
+        assert_eq!(
+            kinds_and_texts(&diff),
+            vec![("equal", "a"), ("removed", "b"), ("equal", "c"), ("added", "x"), ("equal", "d"), ("added", "e")],
+        );
+    }
+
+    #[test]
+    fn diff_lines_handles_empty_texts() {
+        assert!(diff_lines("", "").unwrap().is_empty());
+        assert_eq!(kinds_and_texts(&diff_lines("", "a").unwrap()), vec![("added", "a")]);
+        assert_eq!(kinds_and_texts(&diff_lines("a", "").unwrap()), vec![("removed", "a")]);
+    }
+
+    #[test]
+    fn diff_lines_refuses_texts_that_are_too_different() {
+        let old: String = (0..5000).map(|i| format!("old {}\n", i)).collect();
+        let new: String = (0..5000).map(|i| format!("new {}\n", i)).collect();
+        assert!(diff_lines(&old, &new).is_err());
+    }
+}
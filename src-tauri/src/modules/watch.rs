@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read},
+    path::Path,
+    sync::{mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError}, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use ssh2::{Channel, Session, Sftp};
+use tauri::{command, AppHandle, Manager, State};
+
+use super::ssh_connection::{get_remote_dirs_and_session, run_remote_command, shell_quote};
+
+/// Watched directories, shared between the commands and the threads watching them.
+#[derive(Default)]
+pub struct DirectoryWatches(Mutex<OpenWatches>);
+
+/// Watched directories, keyed by ID, with the user who is watching each one and the sender stopping it.
+#[derive(Default)]
+struct OpenWatches {
+    last_id: u32,
+    watches: HashMap<u32, (String, Sender<()>)>,
+}
+
+/// How a directory is watched: by `inotifywait` running on the Pi, or by comparing listings.
+enum Watcher {
+    Inotify(Channel),
+    Polling(Sftp, DirectorySnapshot),
+}
+
+/// Size, modification time and whether it is a directory, for each entry of a directory.
+type DirectorySnapshot = HashMap<String, (u64, u64, bool)>;
+
+/// Struct to represent a change in a watched directory, emitted as a "directory-change" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryChange {
+    pub id: u32,
+    /// Either "created", "modified" or "deleted"
+    pub kind: String,
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Struct to represent the end of watching a directory, emitted as a "directory-watch-end" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryWatchEnd {
+    pub id: u32,
+    /// Error that stopped the watch, or None if it was stopped by the user
+    pub error: Option<String>,
+}
+
+const INOTIFY_EVENTS: &str = "create,delete,moved_to,moved_from,close_write";
+const INOTIFY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(2);
+
+//================================================================================================
+//                              Commands for watching directories
+//================================================================================================
+
+/// Command to watch the current directory for changes, emitted as "directory-change" events until the watch is stopped.
+/// Uses `inotifywait` on the Raspberry Pi where available, and otherwise compares listings of the directory every 2 seconds.
+/// A "directory-watch-end" event is emitted when the watch stops.
+///
+/// * `Input`: User's name, current path, app handle for emitting events, and the watched directories
+/// * `Output`: ID of the watch
+#[command]
+pub async fn watch_directory(user_name: String, current_path: Vec<String>, app_handle: AppHandle, watches: State<'_, DirectoryWatches>) -> Result<u32, String> {
+    let (mut session, _, current_remote_dir) = get_remote_dirs_and_session(user_name.clone(), current_path).await?;
+
+    let (_, exit_status) = run_remote_command(&mut session, "command -v inotifywait")?;
+    let watcher = if exit_status == 0 {
+        // A terminal makes inotifywait exit when the channel is closed, instead of lingering on the Pi
+        let mut channel = session.channel_session().map_err(|e| format!("Failed to open channel: {}", e))?;
+        channel.request_pty("dumb", None, None).map_err(|e| format!("Failed to request terminal: {}", e))?;
+        let command = format!("inotifywait -m -q -e {} --format '%e/%f' -- {}", INOTIFY_EVENTS, shell_quote(&current_remote_dir));
+        channel.exec(&command).map_err(|e| format!("Failed to execute command: {}", e))?;
+        Watcher::Inotify(channel)
+    } else {
+        let sftp = session.sftp().map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+        let snapshot = take_snapshot(&sftp, &current_remote_dir)?;
+        Watcher::Polling(sftp, snapshot)
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let id = {
+        let mut watches = watches.0.lock().map_err(|_| "Watched directories are poisoned".to_string())?;
+        watches.last_id += 1;
+        let id = watches.last_id;
+        watches.watches.insert(id, (user_name.to_lowercase(), sender));
+        id
+    };
+
+    thread::spawn(move || {
+        let result = match watcher {
+            Watcher::Inotify(mut channel) => watch_with_inotify(id, &session, &mut channel, receiver, &app_handle),
+            Watcher::Polling(sftp, snapshot) => watch_with_snapshots(id, &sftp, &current_remote_dir, snapshot, receiver, &app_handle),
+        };
+        if let Ok(mut watches) = app_handle.state::<DirectoryWatches>().0.lock() {
+            watches.watches.remove(&id);
+        }
+        app_handle.emit_all("directory-watch-end", DirectoryWatchEnd { id, error: result.err() }).unwrap();
+    });
+
+    Ok(id)
+}
+
+/// Command to stop watching a directory.
+///
+/// * `Input`: User's name, ID of the watch, and the watched directories
+/// * `Output`: None
+#[command]
+pub async fn unwatch_directory(user_name: String, id: u32, watches: State<'_, DirectoryWatches>) -> Result<(), String> {
+    let mut watches = watches.0.lock().map_err(|_| "Watched directories are poisoned".to_string())?;
+    let is_owner = watches.watches.get(&id).map(|(owner, _)| owner.eq_ignore_ascii_case(&user_name)).unwrap_or(false);
+    if !is_owner {
+        return Err(format!("Directory watch {} is not active", id));
+    }
+    if let Some((_, sender)) = watches.watches.remove(&id) {
+        sender.send(()).ok();
+    }
+    Ok(())
+}
+
+//================================================================================================
+//                              Helper functions for watching directories
+//================================================================================================
+
+/// Relays the events printed by `inotifywait` until the watch is stopped or `inotifywait` exits.
+/// The session is switched to non-blocking mode so reading does not hold up stopping the watch.
+///
+/// * `Input`: Watch ID, SSH session, channel running `inotifywait`, stop receiver, and app handle for emitting events
+/// * `Output`: None, or an error if the directory can no longer be watched
+fn watch_with_inotify(id: u32, session: &Session, channel: &mut Channel, receiver: Receiver<()>, app_handle: &AppHandle) -> Result<(), String> {
+    let mut buffer = [0; 4096];
+    let mut pending = vec![];
+    session.set_blocking(false);
+    let result = loop {
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            Ok(()) | Err(TryRecvError::Disconnected) => break Ok(()),
+        }
+        match channel.read(&mut buffer) {
+            Ok(0) if channel.eof() => break Err("inotifywait stopped watching the directory".to_string()),
+            Ok(0) => thread::sleep(INOTIFY_POLL_INTERVAL),
+            Ok(read) => {
+                pending.extend_from_slice(&buffer[..read]);
+                let complete = pending.iter().rposition(|byte| *byte == b'\n').map(|position| position + 1).unwrap_or(0);
+                for line in String::from_utf8_lossy(&pending[..complete]).lines() {
+                    if let Some((kind, name, is_dir)) = parse_inotify_event(line) {
+                        let change = DirectoryChange { id, kind: kind.to_string(), name: name.to_string(), is_dir };
+                        app_handle.emit_all("directory-change", change).unwrap();
+                    }
+                }
+                pending.drain(..complete);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(INOTIFY_POLL_INTERVAL),
+            Err(e) => break Err(format!("Failed to read from channel: {}", e)),
+        }
+    };
+    session.set_blocking(true);
+    channel.close().ok();
+    result
+}
+
+/// Parses an event printed by `inotifywait --format '%e/%f'`, e.g. `CREATE,ISDIR/photos`.
+///
+/// * `Input`: Line of output, possibly ending with a carriage return from the terminal
+/// * `Output`: Kind of change, entry name, and whether the entry is a directory, or None for other output
+fn parse_inotify_event(line: &str) -> Option<(&'static str, &str, bool)> {
+    let (events, name) = line.trim_end_matches('\r').split_once('/')?;
+    let events: Vec<&str> = events.split(',').collect();
+    let kind = if events.contains(&"CREATE") || events.contains(&"MOVED_TO") {
+        "created"
+    } else if events.contains(&"DELETE") || events.contains(&"MOVED_FROM") {
+        "deleted"
+    } else if events.contains(&"CLOSE_WRITE") {
+        "modified"
+    } else {
+        return None;
+    };
+    Some((kind, name, events.contains(&"ISDIR")))
+}
+
+/// Compares listings of a directory at a fixed interval until the watch is stopped, emitting the differences.
+///
+/// * `Input`: Watch ID, SFTP session, remote directory, its current listing, stop receiver, and app handle for emitting events
+/// * `Output`: None, or an error if the directory can no longer be read
+fn watch_with_snapshots(id: u32, sftp: &Sftp, remote_dir: &str, mut snapshot: DirectorySnapshot, receiver: Receiver<()>, app_handle: &AppHandle) -> Result<(), String> {
+    loop {
+        match receiver.recv_timeout(SNAPSHOT_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let current = take_snapshot(sftp, remote_dir)?;
+        let emit = |kind: &str, name: &str, is_dir: bool| {
+            let change = DirectoryChange { id, kind: kind.to_string(), name: name.to_string(), is_dir };
+            app_handle.emit_all("directory-change", change).unwrap();
+        };
+        for (name, entry) in &current {
+            match snapshot.get(name) {
+                None => emit("created", name, entry.2),
+                Some(previous) if previous != entry => emit("modified", name, entry.2),
+                Some(_) => {}
+            }
+        }
+        for (name, entry) in &snapshot {
+            if !current.contains_key(name) {
+                emit("deleted", name, entry.2);
+            }
+        }
+        snapshot = current;
+    }
+}
+
+/// Lists a directory with the size and modification time of each entry.
+///
+/// * `Input`: SFTP session and remote directory
+/// * `Output`: Snapshot of the directory
+fn take_snapshot(sftp: &Sftp, remote_dir: &str) -> Result<DirectorySnapshot, String> {
+    let entries = sftp.readdir(Path::new(remote_dir)).map_err(|e| format!("Failed to read directory {}: {}", remote_dir, e))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((name, (stat.size.unwrap_or(0), stat.mtime.unwrap_or(0), stat.is_dir())))
+        })
+        .collect())
+}
@@ -5,11 +5,15 @@
  * @property {string} name - The user name.
  * @property {string} password - The user password.
  * @property {number} storage_limit - The storage limit for the user.
+ * @property {string} [role] - The user's role, "admin" for admin users.
+ * @property {string[]} [actions] - The names of the remote actions the user may run.
  */
 export interface User {
     name: string;
     password: string;
     storage_limit: number;
+    role?: string;
+    actions?: string[];
 }
 
 /**
@@ -18,14 +22,403 @@ export interface User {
  * @interface FileInfo
  * @property {string} name - The file name.
  * @property {string} file_type - The file type.
+ * @property {string | null} mime_type - The MIME type detected from the file contents.
+ * @property {FileKind} kind - The kind of entry.
  * @property {number} size - The file size.
- * @property {string} last_modified - The last modified date.
+ * @property {string} last_modified - The last modified date (ISO-8601).
+ * @property {string} last_accessed - The last accessed date (ISO-8601).
+ * @property {number} mode - The Unix permission bits.
+ * @property {string} permissions - The permission bits as shown by `ls -l`.
+ * @property {number} uid - The owner's user id.
+ * @property {number} gid - The owner's group id.
+ * @property {string | null} owner - The owner's user name.
+ * @property {string | null} group - The owner's group name.
+ * @property {string | null} symlink_target - The target of a symbolic link.
+ * @property {FileKind | null} symlink_target_kind - The kind of entry a symbolic link points to.
+ * @property {boolean} is_hidden - Whether the file name starts with a dot.
  */
 export interface FileInfo {
     name: string;
     file_type: string;
+    mime_type: string | null;
+    kind: FileKind;
     size: number;
     last_modified: string;
+    last_accessed: string;
+    mode: number;
+    permissions: string;
+    uid: number;
+    gid: number;
+    owner: string | null;
+    group: string | null;
+    symlink_target: string | null;
+    symlink_target_kind: FileKind | null;
+    is_hidden: boolean;
+}
+
+/**
+ * Kind of entry a FileInfo describes.
+ */
+export type FileKind = 'file' | 'dir' | 'symlink' | 'other';
+
+/**
+ * One page of a folder listing returned by list_directory, with the number of entries across all pages.
+ */
+export interface DirectoryPage {
+    files: FileInfo[];
+    total: number;
+    offset: number;
+}
+
+/**
+ * FileContent interface.
+ * 
+ * @interface FileContent
+ * @property {string} content - The text content of the file, with `\n` line endings.
+ * @property {string} version - The version token of the file, passed back when saving.
+ * @property {TextFormat} format - How the text is stored on the Pi.
+ */
+export interface FileContent {
+    content: string;
+    version: string;
+    format: TextFormat;
+}
+
+/**
+ * TextFormat interface.
+ * 
+ * @interface TextFormat
+ * @property {string} encoding - The encoding: utf-8, utf-16le, utf-16be or latin-1.
+ * @property {boolean} has_bom - Whether the file starts with a byte order mark.
+ * @property {string} line_ending - The line ending style: lf, crlf or cr.
+ */
+export interface TextFormat {
+    encoding: string;
+    has_bom: boolean;
+    line_ending: string;
+}
+
+/**
+ * Error returned when saving a file.
+ * A conflict means the file changed on the Pi since it was read.
+ */
+export type SaveError =
+    | { kind: 'conflict'; current_version: string | null }
+    | { kind: 'failed'; message: string };
+
+/**
+ * Usage of a filesystem mounted on the Pi, in bytes.
+ */
+export interface FilesystemUsage {
+    filesystem: string;
+    mount_point: string;
+    total: number;
+    used: number;
+    available: number;
+    percent_used: number;
+}
+
+/**
+ * Storage report returned by get_storage_report, in bytes.
+ */
+export interface StorageReport {
+    filesystems: FilesystemUsage[];
+    user_used: number;
+    user_limit: number | null;
+    user_percent_used: number | null;
+    warning_percent: number;
+}
+
+/**
+ * Size of a folder and its subfolders, largest first.
+ */
+export interface FolderSize {
+    name: string;
+    path: string;
+    size: number;
+    children: FolderSize[];
+}
+
+/**
+ * Breakdown of the space used in a user's directory, returned by get_storage_breakdown.
+ */
+export interface StorageBreakdown {
+    root: FolderSize;
+    largest_files: { path: string; size: number }[];
+    computed_at: string;
+}
+
+/**
+ * Payload of the "storage-warning" event.
+ */
+export interface StorageWarning {
+    scope: 'filesystem' | 'user';
+    name: string;
+    percent_used: number;
+    warning_percent: number;
+}
+
+/**
+ * Files with identical contents, paths relative to the user's directory.
+ */
+export interface DuplicateGroup {
+    hash: string;
+    size: number;
+    paths: string[];
+}
+
+/**
+ * Duplicates returned by find_duplicates, with the bytes freed by keeping one file per group.
+ */
+export interface DuplicateReport {
+    groups: DuplicateGroup[];
+    reclaimable: number;
+}
+
+/**
+ * Space recovered by resolve_duplicates; trashed bytes are only freed once the recycle bin is emptied.
+ */
+export interface DuplicatesResolved {
+    freed: number;
+    trashed: number;
+}
+
+/**
+ * Thumbnail of an image file, as a JPEG data URL.
+ */
+export interface Thumbnail {
+    name: string;
+    data_url: string;
+}
+
+/**
+ * Payload of the "thumbnail-error" event, for an image whose thumbnail could not be made.
+ */
+export interface ThumbnailError {
+    name: string;
+    error: string;
+}
+
+/**
+ * Metadata of an image, audio or video file, returned by get_media_metadata.
+ * Width and height are as stored, before applying the EXIF orientation.
+ */
+export interface MediaMetadata {
+    mime_type: string;
+    width: number | null;
+    height: number | null;
+    exif: ExifMetadata | null;
+    streams: StreamMetadata | null;
+}
+
+/**
+ * EXIF fields of a photo.
+ */
+export interface ExifMetadata {
+    camera_make: string | null;
+    camera_model: string | null;
+    lens_model: string | null;
+    capture_date: string | null;
+    orientation: number | null;
+    gps: { latitude: number; longitude: number; altitude: number | null } | null;
+    exposure_time: string | null;
+    f_number: string | null;
+    focal_length: string | null;
+    iso: number | null;
+}
+
+/**
+ * Container and streams of an audio or video file, as reported by ffprobe.
+ */
+export interface StreamMetadata {
+    duration: number | null;
+    format: string | null;
+    bit_rate: number | null;
+    streams: {
+        kind: string;
+        codec: string | null;
+        width: number | null;
+        height: number | null;
+        frame_rate: number | null;
+        sample_rate: number | null;
+        channels: number | null;
+    }[];
+}
+
+/**
+ * Payload of the "terminal-output" event.
+ */
+export interface TerminalOutput {
+    id: number;
+    data: string;
+}
+
+/**
+ * Payload of the "terminal-exit" event, with a null exit status if the connection was lost.
+ */
+export interface TerminalExit {
+    id: number;
+    exit_status: number | null;
+}
+
+/**
+ * Remote action the user may run, returned by list_actions.
+ */
+export interface ActionInfo {
+    name: string;
+    description: string | null;
+}
+
+/**
+ * Payload of the "action-output" event, with the ID of the run it belongs to.
+ */
+export interface ActionOutput {
+    run_id: number;
+    action: string;
+    stream: 'stdout' | 'stderr';
+    data: string;
+}
+
+/**
+ * Payload of the "action-finished" event for the run ID returned by run_action,
+ * with a null exit status if the command was stopped or failed.
+ */
+export interface ActionFinished {
+    run_id: number;
+    exit_status: number | null;
+    timed_out: boolean;
+    error: string | null;
+}
+
+/**
+ * Memory or swap usage in bytes.
+ */
+export interface MemoryUsage {
+    total: number;
+    used: number;
+    available: number;
+}
+
+/**
+ * Throttling flags reported by vcgencmd; the "_occurred" flags stay set since boot.
+ */
+export interface ThrottleStatus {
+    raw: number;
+    under_voltage: boolean;
+    frequency_capped: boolean;
+    throttled: boolean;
+    soft_temperature_limit: boolean;
+    under_voltage_occurred: boolean;
+    frequency_capped_occurred: boolean;
+    throttled_occurred: boolean;
+    soft_temperature_limit_occurred: boolean;
+}
+
+/**
+ * Network interface with its addresses in CIDR notation.
+ */
+export interface NetworkInterface {
+    name: string;
+    addresses: string[];
+}
+
+/**
+ * Health of the Raspberry Pi, returned by get_system_status and emitted as the "system-status" event.
+ * Fields are null when the Pi does not report them.
+ */
+export interface SystemStatus {
+    cpu_temperature: number | null;
+    load_average: [number, number, number] | null;
+    cpu_count: number | null;
+    memory: MemoryUsage | null;
+    swap: MemoryUsage | null;
+    uptime: number | null;
+    throttling: ThrottleStatus | null;
+    network_interfaces: NetworkInterface[];
+}
+
+/**
+ * systemd unit and its state, returned by list_services and manage_service.
+ */
+export interface ServiceUnit {
+    name: string;
+    description: string;
+    load_state: string;
+    active_state: string;
+    sub_state: string;
+    unit_file_state: string;
+}
+
+/**
+ * Start of following a file, returned by tail_file with its last lines.
+ */
+export interface TailStart {
+    id: number;
+    lines: string[];
+}
+
+/**
+ * Payload of the "tail-output" event; truncated is set when the file shrank or was replaced, e.g. by log rotation, and is followed again from its start.
+ */
+export interface TailOutput {
+    id: number;
+    lines: string[];
+    truncated: boolean;
+}
+
+/**
+ * Payload of the "tail-end" event, with a null error if following was stopped by the user.
+ */
+export interface TailEnd {
+    id: number;
+    error: string | null;
+}
+
+/**
+ * Payload of the "directory-change" event for a watched directory.
+ */
+export interface DirectoryChange {
+    id: number;
+    kind: 'created' | 'modified' | 'deleted';
+    name: string;
+    is_dir: boolean;
+}
+
+/**
+ * Payload of the "directory-watch-end" event, with a null error if the watch was stopped by the user.
+ */
+export interface DirectoryWatchEnd {
+    id: number;
+    error: string | null;
+}
+
+/**
+ * Local port forwarded to a port on the Pi, with its traffic in bytes.
+ */
+export interface PortForward {
+    id: number;
+    local_port: number;
+    remote_host: string;
+    remote_port: number;
+    bytes_sent: number;
+    bytes_received: number;
+    connections: number;
+}
+
+/**
+ * Payload of the "port-forward-error" event, for a connection the Pi could not open; the forward stays open.
+ */
+export interface PortForwardError {
+    id: number;
+    error: string;
+}
+
+/**
+ * Payload of the "port-forward-end" event, with a null error if the forward was closed by the user.
+ */
+export interface PortForwardEnd {
+    id: number;
+    error: string | null;
 }
 
 /**
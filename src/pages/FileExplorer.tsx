@@ -2,12 +2,12 @@ import React, { useCallback, useEffect, useState } from 'react';
 import { useLocation } from 'react-router-dom';
 import { Container, Box, Loader, ScrollArea, Table, Group, Modal, TextInput, Textarea, Space } from '@mantine/core';
 import { invoke } from '@tauri-apps/api/tauri';
-import { fetchFiles, formatDate, formatFileSize, getIconByFileExtension } from '../utils';
+import { fetchFiles, FILES_PAGE_SIZE, formatDate, formatFileSize, getIconByFileExtension, getMediaUrl, isFolder, THUMBNAIL_MIME_TYPES } from '../utils';
 import { IoMdCloudDownload, IoMdCloudUpload, IoMdRefresh } from 'react-icons/io';
 import { notifications } from '@mantine/notifications';
 import { IoAdd, IoAlertCircle, IoCheckmarkCircle } from 'react-icons/io5';
 import { BreadcrumbItem, Breadcrumbs, Button } from '@nextui-org/react';
-import { FileInfo, User } from '../interfaces';
+import { DirectoryPage, FileContent, FileInfo, SaveError, Thumbnail, User } from '../interfaces';
 import DownloadProgress from '../components/DownloadProgress';
 import { open } from '@tauri-apps/api/dialog';
 import { MdDeleteForever, MdEdit } from "react-icons/md";
@@ -24,6 +24,8 @@ const FileExplorer: React.FC = (): JSX.Element => {
     const location = useLocation();                             // Get the location object
     const user = location.state?.user as User;                  // Get the user object from the location state
     const [files, setFiles] = useState<FileInfo[]>([]);         // Initialize the files state
+    const [totalFiles, setTotalFiles] = useState(0);            // Number of entries in the current folder, across all pages
+    const [isLoadingMore, setIsLoadingMore] = useState(false);  // State for fetching the next page of the folder
     const [currentPath, setCurrentPath] = useState<string[]>([]); // Initialize the current path state
     const [loading, setLoading] = useState(true);               // Initialize the loading state
     const [isUploading, setIsUploading] = useState(false);      // Initialize the isUploading state
@@ -38,13 +40,27 @@ const FileExplorer: React.FC = (): JSX.Element => {
     const [isFileOpen, setIsFileOpen] = useState(false);        // State for handling the file open modal
     const [fileContent, setFileContent] = useState('');         // State for storing the file content
     const [currentFile, setCurrentFile] = useState('');         // State for storing the current file name
+    const [fileVersion, setFileVersion] = useState<string | null>(null); // State for storing the version of the opened file
     const [storageUsed, setStorageUsed] = useState<number | null>(null); // State for storing the storage used
+    const [thumbnails, setThumbnails] = useState<Record<string, string>>({}); // State for storing the image thumbnails by file name
+    const [previewFile, setPreviewFile] = useState<FileInfo | null>(null); // State for the media file being previewed
 
-    // Fetch the files from the Raspberry Pi
+    // Fetch the first page of files from the Raspberry Pi
     const fetchFilesCallback = useCallback((path: string[]) => {
-        fetchFiles(user, path, setFiles, setLoading, setError);
+        fetchFiles(user, path, 0, (page: DirectoryPage) => {
+            setFiles(page.files);
+            setTotalFiles(page.total);
+        }, setLoading, setError);
     }, [user]);
 
+    // Fetch the next page of files, keeping the ones already shown
+    const loadMoreFiles = () => {
+        fetchFiles(user, currentPath, files.length, (page: DirectoryPage) => {
+            setFiles(previousFiles => [...previousFiles, ...page.files]);
+            setTotalFiles(page.total);
+        }, setIsLoadingMore, setError);
+    };
+
     const updateStorageUsed = useCallback(() => {
         if (user) {
             invoke('get_storage_used', { userName: user.name.toLowerCase() })
@@ -66,6 +82,29 @@ const FileExplorer: React.FC = (): JSX.Element => {
         fetchFilesCallback(currentPath);  // Initial fetch
     }, [user, currentPath, fetchFilesCallback]);
 
+    // Fetch the thumbnails of the images in the current folder
+    useEffect(() => {
+        setThumbnails({});
+        const fileNames = files.filter(file => file.kind === 'file' && THUMBNAIL_MIME_TYPES.includes(file.mime_type ?? '')).map(file => file.name);
+        if (!user || fileNames.length === 0) {
+            return;
+        }
+        let cancelled = false; // Ignore thumbnails of a folder the user has already left
+        invoke('get_thumbnails', { userName: user.name.toLowerCase(), currentPath, fileNames })
+            .then((result: unknown) => {
+                if (!cancelled) {
+                    const entries = (result as Thumbnail[]).map(thumbnail => [thumbnail.name, thumbnail.data_url]);
+                    setThumbnails(Object.fromEntries(entries));
+                }
+            })
+            .catch(err => {
+                console.error('Failed to get thumbnails:', err);
+            });
+        return () => {
+            cancelled = true;
+        };
+    }, [user, currentPath, files]);
+
     // Handle the download of selected files
     const handleDownload = () => {
         setIsDownloading(true);
@@ -276,7 +315,7 @@ const FileExplorer: React.FC = (): JSX.Element => {
         const file = files.find(f => f.name === selectedFile);
 
         if (file) {
-            if (file.file_type === 'Folder') {
+            if (isFolder(file)) {
                 // Navigate into the folder
                 setCurrentPath([...currentPath, file.name]);
                 setSelectedFiles(new Set()); // Clear the selected files
@@ -284,8 +323,10 @@ const FileExplorer: React.FC = (): JSX.Element => {
                 // Read the file content
                 setCurrentFile(file.name);
                 invoke('read_file', { userName: user.name.toLowerCase(), currentPath, fileName: file.name })
-                    .then((content: unknown) => {
-                        setFileContent(content as string);
+                    .then((result: unknown) => {
+                        const { content, version } = result as FileContent;
+                        setFileContent(content);
+                        setFileVersion(version);
                         setIsFileOpen(true);
                     })
                     .catch(err => {
@@ -301,8 +342,8 @@ const FileExplorer: React.FC = (): JSX.Element => {
         }
     };
 
-    const handleSaveFile = () => {
-        invoke('save_file', { userName: user.name.toLowerCase(), currentPath, fileName: currentFile, fileContent })
+    const handleSaveFile = (force = false) => {
+        invoke('save_file', { userName: user.name.toLowerCase(), currentPath, fileName: currentFile, fileContent, expectedVersion: fileVersion, force })
             .then(() => {
                 notifications.show({
                     message: `File saved successfully!`,
@@ -313,12 +354,20 @@ const FileExplorer: React.FC = (): JSX.Element => {
                 setIsFileOpen(false);
                 setCurrentFile('');
                 setFileContent('');
+                setFileVersion(null);
                 updateStorageUsed();
             })
-            .catch(err => {
+            .catch((err: SaveError) => {
                 console.error('Failed to save file:', err);
+                if (err.kind === 'conflict') {
+                    const overwrite = window.confirm('This file was changed on the Pi since you opened it. Overwrite those changes?');
+                    if (overwrite) {
+                        handleSaveFile(true);
+                    }
+                    return;
+                }
                 notifications.show({
-                    message: `Failed to save file: ${err}`,
+                    message: `Failed to save file: ${err.message}`,
                     icon: <IoAlertCircle />,
                     autoClose: 5000,
                     color: 'red'
@@ -343,9 +392,22 @@ const FileExplorer: React.FC = (): JSX.Element => {
     // Handle double click to navigate into folder
     const handleDoubleClick = (fileName: string) => {
         const file = files.find(f => f.name === fileName);
-        if (file && file.file_type === 'Folder') {
+        if (file && isFolder(file)) {
             setCurrentPath([...currentPath, fileName]);
             setSelectedFiles(new Set()); // Clear the selected files
+        } else if (file && /^(image|video|audio)\//.test(file.mime_type ?? '')) {
+            // Stream media files instead of downloading them, once the session serving them is open
+            invoke('open_media_session', { userName: user.name.toLowerCase() })
+              .then(() => setPreviewFile(file))
+              .catch(err => {
+                console.error('Failed to open media session:', err);
+                notifications.show({
+                  message: `Failed to preview file: ${err}`,
+                  icon: <IoAlertCircle />,
+                  autoClose: 5000,
+                  color: 'red'
+                });
+              });
         }
     };
 
@@ -492,18 +554,10 @@ const FileExplorer: React.FC = (): JSX.Element => {
                                     </Table.Tr>
                                 </Table.Thead>
                                 <Table.Tbody style={{ userSelect: 'none', WebkitUserSelect: 'none', MozUserSelect: 'none' }}>
-                                    {files.sort((a, b) => {
-                                        if (a.file_type === 'Folder' && b.file_type !== 'Folder') {
-                                            return -1;
-                                        }
-                                        if (b.file_type === 'Folder' && a.file_type !== 'Folder') {
-                                            return 1;
-                                        }
-                                        return a.name.localeCompare(b.name);
-                                    }).map(file => {
+                                    {files.map(file => {
                                         // Remove the extension from the file name
                                         const [name, extension] = file.name.split('.');
-                                        const icon = file.file_type !== 'Folder' ? getIconByFileExtension(extension) : '📁';
+                                        const icon = isFolder(file) ? '📁' : file.kind === 'symlink' ? '🔗' : getIconByFileExtension(extension);
                                         const displayName = `${icon} ${name}`;
 
                                         return (
@@ -516,11 +570,18 @@ const FileExplorer: React.FC = (): JSX.Element => {
                                                     cursor: 'pointer'
                                                 }}
                                             >
-                                                <Table.Td style={{ color: 'white' }}>{displayName}</Table.Td>
-                                                <Table.Td style={{ color: 'white' }}>{formatDate(Number(file.last_modified))}</Table.Td>
+                                                <Table.Td style={{ color: 'white' }}>
+                                                {thumbnails[file.name] ? (
+                                                    <Group gap="xs" wrap="nowrap">
+                                                        <img src={thumbnails[file.name]} alt="" style={{ width: 24, height: 24, objectFit: 'cover', borderRadius: 2 }} />
+                                                        {name}
+                                                    </Group>
+                                                ) : displayName}
+                                                </Table.Td>
+                                                <Table.Td style={{ color: 'white' }}>{formatDate(Date.parse(file.last_modified) / 1000)}</Table.Td>
                                                 <Table.Td style={{ color: 'white' }}>{file.file_type}</Table.Td>
                                                 <Table.Td style={{ color: 'white' }}>
-                                                {file.file_type !== 'Folder' ? formatFileSize(file.size) : ''}
+                                                {!isFolder(file) ? formatFileSize(file.size) : ''}
                                                 </Table.Td>
                                             </Table.Tr>
                                         );
@@ -528,6 +589,13 @@ const FileExplorer: React.FC = (): JSX.Element => {
                                 </Table.Tbody>
                             </Table>
                         </Table.ScrollContainer>
+                        {files.length < totalFiles && (
+                            <Group justify="center" my="xs">
+                                <Button onClick={loadMoreFiles} isLoading={isLoadingMore} radius="none" size="sm">
+                                    Load {Math.min(FILES_PAGE_SIZE, totalFiles - files.length)} more ({files.length} of {totalFiles})
+                                </Button>
+                            </Group>
+                        )}
                     </ScrollArea>
                 )}
             </Container>
@@ -647,12 +715,33 @@ const FileExplorer: React.FC = (): JSX.Element => {
                         color="success"
                         variant='flat'
                         radius='none'
-                        onClick={handleSaveFile}
+                        onClick={() => handleSaveFile()}
                     >
                         Save
                     </Button>
                 </Group>
             </Modal>
+
+            {/* Media Preview Modal */}
+            <Modal
+                opened={previewFile !== null}
+                onClose={() => setPreviewFile(null)}
+                title={previewFile?.name}
+                centered
+                size="xl"
+                radius={0}
+            >
+                {previewFile && (() => {
+                    const src = getMediaUrl(user, currentPath, previewFile.name);
+                    if (previewFile.mime_type?.startsWith('video/')) {
+                        return <video src={src} controls autoPlay style={{ width: '100%' }} />;
+                    }
+                    if (previewFile.mime_type?.startsWith('audio/')) {
+                        return <audio src={src} controls autoPlay style={{ width: '100%' }} />;
+                    }
+                    return <img src={src} alt={previewFile.name} style={{ width: '100%' }} />;
+                })()}
+            </Modal>
         </Container>
     );
 };
@@ -1,6 +1,6 @@
 import { toRgba } from "@mantine/core";
-import { invoke } from "@tauri-apps/api/tauri";
-import { FileInfo, User } from "./interfaces";
+import { convertFileSrc, invoke } from "@tauri-apps/api/tauri";
+import { DirectoryPage, FileInfo, User } from "./interfaces";
 
 /**
  * Convert a hex color to an rgba color.
@@ -73,14 +73,55 @@ export const getIconByFileExtension = (extension: string): string => {
     return "";
 };
 
+/**
+ * Check whether a file is a folder, or a symbolic link to one.
+ *
+ * @param {FileInfo} file - The file.
+ * @returns {boolean} True if the file can be opened as a folder.
+ */
+export const isFolder = (file: FileInfo): boolean => {
+	return file.kind === 'dir' || (file.kind === 'symlink' && file.symlink_target_kind === 'dir');
+};
+
+/**
+ * Build the URL streaming a file from the Pi through the `pi://` protocol.
+ * The URL can be used directly as the source of an image, video or audio element.
+ *
+ * @param {User} user - The user.
+ * @param {string[]} path - The current path.
+ * @param {string} fileName - The file name.
+ * @returns {string} The media URL.
+ */
+export const getMediaUrl = (user: User, path: string[], fileName: string): string => {
+	return convertFileSrc([user.name.toLowerCase(), ...path, fileName].join('/'), 'pi');
+};
 
-export const fetchFiles = (user: User, path: string[], setFiles: (files: FileInfo[]) => void, setLoading: (loading: boolean) => void, setError: (error: string | null) => void) => {
+/**
+ * Number of entries fetched per page of a folder listing.
+ */
+export const FILES_PAGE_SIZE = 200;
+
+/**
+ * MIME types of the images get_thumbnails makes thumbnails of.
+ */
+export const THUMBNAIL_MIME_TYPES = ['image/jpeg', 'image/png', 'image/webp'];
+
+/**
+ * Fetch one page of a folder listing from the Pi, folders first and sorted by name.
+ *
+ * @param {User} user - The user.
+ * @param {string[]} path - The current path.
+ * @param {number} offset - The index of the first entry to fetch.
+ * @param {(page: DirectoryPage) => void} setPage - Called with the fetched page.
+ * @param {(loading: boolean) => void} setLoading - Called when the fetch starts and ends.
+ * @param {(error: string | null) => void} setError - Called with the error if the fetch fails.
+ */
+export const fetchFiles = (user: User, path: string[], offset: number, setPage: (page: DirectoryPage) => void, setLoading: (loading: boolean) => void, setError: (error: string | null) => void) => {
     if (user) {
         setLoading(true);
-        const fullPath = path.length === 0 ? '' : path.join('/');
-        invoke<FileInfo[]>('connect_to_pi', { userName: user.name.toLowerCase(), path: fullPath })
-            .then((files) => {
-                setFiles(files);
+        invoke<DirectoryPage>('list_directory', { userName: user.name.toLowerCase(), currentPath: path, options: { offset, limit: FILES_PAGE_SIZE } })
+            .then((page) => {
+                setPage(page);
                 setLoading(false);
             })
             .catch(err => {